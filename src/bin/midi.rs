@@ -0,0 +1,296 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Command line tool to inspect and convert Midi data
+
+extern crate midi;
+extern crate serde_json;
+
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+
+use midi::{Decoder, Message, Rmid, Smf, TimeFormat, ToRawMessages, read_syx};
+
+const USAGE: &str = "Usage:
+  midi decode [HEX...]            decode hex bytes (from the arguments or stdin) to messages
+  midi encode MESSAGE...          encode a message, e.g. 'note_on ch1 C4 vel=100', to hex
+  midi dump FILE                  print a .mid, .rmi or .syx file as text
+  midi convert INPUT OUTPUT [--absolute]
+                                  convert between Standard Midi Files (.mid), text (.txt)
+                                  and JSON (.json), the input may also be an RMID file (.rmi)
+
+Use - for stdin or stdout.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let result = match args.split_first() {
+        Some((&"decode", rest)) => decode(rest),
+        Some((&"encode", rest)) if !rest.is_empty() => encode(rest),
+        Some((&"dump", &[path])) => dump(path),
+        Some((&"convert", &[input, output])) => {
+            convert(input, output, TimeFormat::Delta).map(|_| String::new())
+        },
+        Some((&"convert", &[input, output, "--absolute"])) => {
+            convert(input, output, TimeFormat::Absolute).map(|_| String::new())
+        },
+        Some((&"help", _)) | Some((&"--help", _)) | Some((&"-h", _)) => Ok(format!("{}\n", USAGE)),
+        _ => Err(USAGE.to_string())
+    };
+    match result {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            let _ = writeln!(io::stderr(), "{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// the commands return the text to print to stdout
+
+fn decode(args: &[&str]) -> Result<String, String> {
+    let text = if args.is_empty() {
+        String::from_utf8_lossy(&read_input("-")?).into_owned()
+    }
+    else {
+        args.join(" ")
+    };
+    let mut output = String::new();
+    for message in Decoder::decode(&parse_hex(&text)?) {
+        let _ = writeln!(output, "{:<24}{}", hex(&message), message);
+    }
+    Ok(output)
+}
+
+fn encode(args: &[&str]) -> Result<String, String> {
+    let message: Message = args.join(" ").parse().map_err(|e| format!("{}", e))?;
+    Ok(format!("{}\n", hex(&message)))
+}
+
+fn dump(path: &str) -> Result<String, String> {
+    let bytes = read_input(path)?;
+    let mut output = String::new();
+    if bytes.starts_with(&[0xF0]) {
+        for message in read_syx(&bytes).map_err(|e| format!("{}", e))? {
+            let _ = writeln!(output, "{}", message);
+        }
+    }
+    else if bytes.starts_with(b"RIFF") {
+        let rmid = Rmid::read(&bytes).map_err(|e| format!("{}", e))?;
+        let fields = [("title", &rmid.info.title),
+                      ("copyright", &rmid.info.copyright),
+                      ("comments", &rmid.info.comments)];
+        for &(name, value) in &fields {
+            if let Some(ref value) = *value {
+                let _ = writeln!(output, "# {}: {}", name, value);
+            }
+        }
+        if rmid.dls.is_some() {
+            output.push_str("# contains a DLS bank\n");
+        }
+        let smf = rmid.smf().map_err(|e| format!("{}", e))?;
+        output.push_str(&smf.to_text(TimeFormat::Delta));
+    }
+    else {
+        output.push_str(&read_smf(&bytes)?.to_text(TimeFormat::Delta));
+    }
+    Ok(output)
+}
+
+fn convert(input: &str, output: &str, time: TimeFormat) -> Result<(), String> {
+    let bytes = read_input(input)?;
+    let smf = if bytes.starts_with(b"MThd") {
+        read_smf(&bytes)?
+    }
+    else if bytes.starts_with(b"RIFF") {
+        let rmid = Rmid::read(&bytes).map_err(|e| format!("{}", e))?;
+        rmid.smf().map_err(|e| format!("{}", e))?
+    }
+    else if extension(input) == Some("json") {
+        serde_json::from_slice(&bytes).map_err(|e| format!("{}", e))?
+    }
+    else {
+        let text = String::from_utf8(bytes).map_err(|e| format!("{}", e))?;
+        Smf::from_text(&text).map_err(|e| format!("{}", e))?
+    };
+
+    let bytes = match extension(output) {
+        Some("mid") | Some("midi") | Some("smf") => {
+            smf.to_bytes().map_err(|e| format!("{}", e))?
+        },
+        Some("json") => {
+            let mut json = serde_json::to_vec_pretty(&smf).map_err(|e| format!("{}", e))?;
+            json.push(b'\n');
+            json
+        },
+        _ => smf.to_text(time).into_bytes()
+    };
+    write_output(output, &bytes)
+}
+
+fn read_smf(bytes: &[u8]) -> Result<Smf, String> {
+    Smf::read(bytes).map_err(|e| format!("{}", e))
+}
+
+fn extension(path: &str) -> Option<&str> {
+    Path::new(path).extension().and_then(|e| e.to_str())
+}
+
+fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    if path == "-" {
+        io::stdin().read_to_end(&mut bytes).map_err(|e| format!("stdin: {}", e))?;
+    }
+    else {
+        bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(bytes)
+}
+
+fn write_output(path: &str, bytes: &[u8]) -> Result<(), String> {
+    if path == "-" {
+        io::stdout().write_all(bytes).map_err(|e| format!("stdout: {}", e))
+    }
+    else {
+        fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn hex(message: &Message) -> String {
+    let bytes: Vec<String> = message.to_raw_messages().iter()
+        .flat_map(|r| r.to_bytes())
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    bytes.join(" ")
+}
+
+/// Parse hex bytes, separated by whitespace or commas, and optionally prefixed by `0x`, e.g.
+/// `90 3C 64`, `0x90,0x3c,0x64` or `903C64`
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+        let invalid = || format!("invalid hex '{}'", token);
+        let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+        if digits.is_empty() || (digits.len() > 2 && digits.len() % 2 == 1) ||
+           !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut i = 0;
+        while i < digits.len() {
+            let pair = digits.get(i..(i + 2).min(digits.len())).ok_or_else(invalid)?;
+            bytes.push(u8::from_str_radix(pair, 16).map_err(|_| invalid())?);
+            i += 2;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const SMF: &[u8] = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60\
+                         MTrk\x00\x00\x00\x0b\x00\x90\x3c\x64\x60\x3c\x00\x00\xff\x2f\x00";
+
+    const TEXT: &str = "smf format=0 division=96 time=delta
+track
+0 note_on ch1 C4 vel=100
+96 ~note_on ch1 C4 vel=0
+0 meta end_of_track
+end_track
+";
+
+    // a directory in the temp directory that is unique to the test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!("midi-cli-{}-{}", process::id(), test));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("90 3C 64"), Ok(vec![0x90, 0x3C, 0x64]));
+        assert_eq!(parse_hex("0x90,0x3c, 0X64"), Ok(vec![0x90, 0x3C, 0x64]));
+        assert_eq!(parse_hex("903C64\n8"), Ok(vec![0x90, 0x3C, 0x64, 0x08]));
+        assert_eq!(parse_hex(""), Ok(vec![]));
+        assert_eq!(parse_hex("0x0x12"), Err("invalid hex '0x0x12'".to_string()));
+        assert_eq!(parse_hex("0x"), Err("invalid hex '0x'".to_string()));
+        assert_eq!(parse_hex("903"), Err("invalid hex '903'".to_string()));
+        assert_eq!(parse_hex("9G"), Err("invalid hex '9G'".to_string()));
+        assert_eq!(parse_hex("+1"), Err("invalid hex '+1'".to_string()));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(&["90 3C 64", "0xB0,0x07,0x5A"]),
+                   Ok(format!("{:<24}note_on ch1 C4 vel=100\n{:<24}cc ch1 volume 90\n",
+                              "90 3C 64", "B0 07 5A")));
+        assert!(decode(&["zz"]).is_err());
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(&["note_on", "ch1", "C4", "vel=100"]), Ok("90 3C 64\n".to_string()));
+        assert!(encode(&["note_on", "ch17"]).is_err());
+    }
+
+    #[test]
+    fn test_dump() {
+        let dir = TempDir::new("dump");
+        let mid = dir.path("dump.mid");
+        fs::write(&mid, SMF).unwrap();
+        assert_eq!(dump(&mid), Ok(TEXT.to_string()));
+
+        let mut rmid = Rmid::new(SMF.to_vec());
+        rmid.info.title = Some("Tune".to_string());
+        let rmi = dir.path("dump.rmi");
+        fs::write(&rmi, rmid.to_bytes()).unwrap();
+        assert_eq!(dump(&rmi), Ok(format!("# title: Tune\n{}", TEXT)));
+
+        let syx = dir.path("dump.syx");
+        fs::write(&syx, b"\xf0\x7e\x7f\x06\x01\xf7").unwrap();
+        assert_eq!(dump(&syx), Ok("sysex 0x7E [7F 06 01]\n".to_string()));
+
+        assert!(dump(&dir.path("missing.mid")).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let dir = TempDir::new("convert");
+        let mid = dir.path("convert.mid");
+        let txt = dir.path("convert.txt");
+        let json = dir.path("convert.json");
+        let out = dir.path("convert_out.mid");
+        fs::write(&mid, SMF).unwrap();
+
+        assert_eq!(convert(&mid, &txt, TimeFormat::Delta), Ok(()));
+        assert_eq!(fs::read_to_string(&txt).unwrap(), TEXT);
+        assert_eq!(convert(&txt, &json, TimeFormat::Delta), Ok(()));
+        assert_eq!(convert(&json, &out, TimeFormat::Delta), Ok(()));
+        assert_eq!(fs::read(&out).unwrap(), SMF);
+
+        assert_eq!(convert(&mid, &txt, TimeFormat::Absolute), Ok(()));
+        assert!(fs::read_to_string(&txt).unwrap().contains("\n96 ~note_on ch1 C4 vel=0\n96 meta"));
+    }
+}
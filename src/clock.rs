@@ -0,0 +1,448 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use constants::{CLOCKS_PER_BEAT, CLOCKS_PER_MIDI_BEAT};
+use types::U14;
+use message::Message;
+use Message::*;
+
+/// The number of clock intervals averaged by a `ClockFollower`, one quarter note
+const DEFAULT_WINDOW: usize = 24;
+
+/// A gap between clocks longer than this (10 BPM) restarts the tempo estimate
+const MAX_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The fastest tempo of a `ClockGenerator`, a clock every 25 microseconds
+const MAX_BPM: f64 = 100_000.0;
+
+/// Whether a sequence is playing
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Transport {
+    Stopped,
+    Playing
+}
+
+/// Follows an incoming Midi clock, estimating its tempo and tracking the transport and position
+///
+/// The tempo is the average over the last quarter note of clocks (by default), which smooths out
+/// the jitter of individual clocks. Times are given by the caller, as the time since any fixed
+/// point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockFollower {
+    window: usize,
+    clocks: VecDeque<Duration>,
+    transport: Transport,
+    position: u32
+}
+
+impl ClockFollower {
+    pub fn new() -> ClockFollower {
+        ClockFollower::with_window(DEFAULT_WINDOW)
+    }
+
+    /// A follower that averages the tempo over `window` clock intervals, a larger window gives a
+    /// steadier tempo but follows tempo changes more slowly
+    ///
+    /// Panics if `window` is 0.
+    pub fn with_window(window: usize) -> ClockFollower {
+        assert!(window > 0, "window must be greater than 0");
+        ClockFollower {
+            window,
+            clocks: VecDeque::with_capacity(window + 1),
+            transport: Transport::Stopped,
+            position: 0
+        }
+    }
+
+    /// Process a message that arrived at `now`
+    ///
+    /// `Start` plays from the start of the song, `Continue` plays from the current position, and
+    /// `SongPositionPointer` moves the position. `TimingClock` advances the position while
+    /// playing, but is used for the tempo whether playing or not.
+    pub fn process(&mut self, now: Duration, message: &Message) {
+        match *message {
+            TimingClock => {
+                if self.clocks.back().is_some_and(|&last| now > last + MAX_INTERVAL) {
+                    self.clocks.clear();
+                }
+                if self.clocks.len() > self.window {
+                    self.clocks.pop_front();
+                }
+                self.clocks.push_back(now);
+                if self.transport == Transport::Playing {
+                    self.position += 1;
+                }
+            },
+            Start => {
+                self.transport = Transport::Playing;
+                self.position = 0;
+            },
+            Continue => self.transport = Transport::Playing,
+            Stop => self.transport = Transport::Stopped,
+            SongPositionPointer(position) => {
+                self.position = position as u32 * CLOCKS_PER_MIDI_BEAT;
+            },
+            SystemReset => *self = ClockFollower::with_window(self.window),
+            _ => ()
+        }
+    }
+
+    /// The estimated tempo in beats per minute, `None` until at least 2 clocks have arrived
+    pub fn bpm(&self) -> Option<f64> {
+        match (self.clocks.front(), self.clocks.back()) {
+            (Some(first), Some(last)) if self.clocks.len() > 1 => {
+                let elapsed = *last - *first;
+                let interval = elapsed.as_secs_f64() / (self.clocks.len() - 1) as f64;
+                if interval > 0.0 {
+                    Some(60.0 / (interval * CLOCKS_PER_BEAT as f64))
+                }
+                else {
+                    None
+                }
+            },
+            _ => None
+        }
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.transport == Transport::Playing
+    }
+
+    /// The number of clocks played since the start of the song (24 per quarter note)
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// The position as a `SongPositionPointer` value, in whole Midi beats (16th notes)
+    pub fn song_position(&self) -> U14 {
+        (self.position / CLOCKS_PER_MIDI_BEAT) as U14
+    }
+}
+
+impl Default for ClockFollower {
+    fn default() -> ClockFollower {
+        ClockFollower::new()
+    }
+}
+
+/// Generates a Midi clock, with `Start`, `Stop`, `Continue` and `SongPositionPointer`
+///
+/// Clock times are calculated from the last tempo change rather than by adding up intervals, and
+/// are exact to the nanosecond for tempos with up to 3 decimal places, so the clock does not drift
+/// however long it runs. Clocks run continuously from the start time, whether the transport is
+/// playing or not. Transport messages are sent with the next clock, so that `Start` is followed
+/// immediately by the first clock of the song.
+///
+/// Swing delays every other 16th note, it is the percentage of each 8th note taken by its first
+/// 16th, 50 being straight.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClockGenerator {
+    millibpm: u64,
+    swing: u64,
+    // the clock and time that the current tempo and swing are measured from
+    anchor: (u64, Duration),
+    next: u64,
+    pending: Vec<Message>,
+    transport: Transport,
+    position: u32
+}
+
+impl ClockGenerator {
+    /// A generator with the first clock at `start`
+    ///
+    /// Panics unless `bpm` is between 0.001 and 100000, the tempo is kept to the nearest 0.001
+    /// BPM.
+    pub fn new(bpm: f64, start: Duration) -> ClockGenerator {
+        ClockGenerator {
+            millibpm: millibpm(bpm),
+            swing: 50,
+            anchor: (0, start),
+            next: 0,
+            pending: Vec::new(),
+            transport: Transport::Stopped,
+            position: 0
+        }
+    }
+
+    /// Change the tempo from the next clock on
+    ///
+    /// Panics unless `bpm` is between 0.001 and 100000, the tempo is kept to the nearest 0.001
+    /// BPM.
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.anchor = (self.next, self.next_time());
+        self.millibpm = millibpm(bpm);
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.millibpm as f64 / 1000.0
+    }
+
+    /// Change the swing from the next clock on
+    ///
+    /// Panics unless `percent` is between 1 and 99.
+    pub fn set_swing(&mut self, percent: u32) {
+        assert!(percent > 0 && percent < 100, "swing must be between 1 and 99");
+        self.anchor = (self.next, self.next_time());
+        self.swing = percent as u64;
+    }
+
+    pub fn swing(&self) -> u32 {
+        self.swing as u32
+    }
+
+    /// Play from the start of the song
+    pub fn start(&mut self) {
+        self.pending.push(Start);
+        self.transport = Transport::Playing;
+        self.position = 0;
+    }
+
+    pub fn stop(&mut self) {
+        if self.transport == Transport::Playing {
+            self.pending.push(Stop);
+            self.transport = Transport::Stopped;
+        }
+    }
+
+    /// Play from the current position
+    pub fn resume(&mut self) {
+        if self.transport == Transport::Stopped {
+            self.pending.push(Continue);
+            self.transport = Transport::Playing;
+        }
+    }
+
+    /// Move to `position` in Midi beats (16th notes), sending a `SongPositionPointer`
+    ///
+    /// The spec only allows the position to change while stopped, so if playing the transport is
+    /// stopped, moved and continued.
+    pub fn relocate(&mut self, position: U14) {
+        let playing = self.transport == Transport::Playing;
+        self.stop();
+        self.pending.push(SongPositionPointer(position));
+        self.position = position as u32 * CLOCKS_PER_MIDI_BEAT;
+        if playing {
+            self.resume();
+        }
+    }
+
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// The number of clocks played since the start of the song
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// The time that the next clock is due
+    pub fn next_time(&self) -> Duration {
+        self.clock_time(self.next)
+    }
+
+    /// Returns the messages that are due by `now`, with the time each one is scheduled for
+    pub fn poll(&mut self, now: Duration) -> Vec<(Duration, Message)> {
+        let mut messages = Vec::new();
+        loop {
+            let time = self.next_time();
+            if time > now {
+                return messages;
+            }
+            messages.extend(self.pending.drain(..).map(|m| (time, m)));
+            messages.push((time, TimingClock));
+            self.next += 1;
+            if self.transport == Transport::Playing {
+                self.position += 1;
+            }
+        }
+    }
+
+    fn clock_time(&self, clock: u64) -> Duration {
+        let (anchor_clock, anchor_time) = self.anchor;
+        let units = (self.swung(clock) - self.swung(anchor_clock)) as u128;
+        // units are 1/600 of a clock, there are 60e9 nanoseconds in a minute, and tempo is in
+        // thousandths of a beat per minute
+        let nanos = units * 60_000_000_000_000 /
+            (self.millibpm as u128 * CLOCKS_PER_BEAT as u128 * 600);
+        anchor_time + Duration::from_nanos(nanos as u64)
+    }
+
+    // the position of a clock in 1/600ths of a clock, with swing applied
+    fn swung(&self, clock: u64) -> u64 {
+        let (eighth, clock) = (clock / 12, clock % 12);
+        let offset = if clock <= 6 {
+            clock * 12 * self.swing
+        }
+        else {
+            72 * self.swing + (clock - 6) * 12 * (100 - self.swing)
+        };
+        eighth * 12 * 600 + offset
+    }
+}
+
+fn millibpm(bpm: f64) -> u64 {
+    assert!((0.001..=MAX_BPM).contains(&bpm), "bpm must be between 0.001 and 100000");
+    (bpm * 1000.0).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn us(us: u64) -> Duration {
+        Duration::from_micros(us)
+    }
+
+    #[test]
+    fn test_bpm() {
+        let mut f = ClockFollower::new();
+        assert_eq!(f.bpm(), None);
+        // 120 BPM is a clock every 20833us, with +/- 1ms of jitter
+        for i in 0..100 {
+            let jitter = if i % 2 == 0 { 1000 } else { 0 };
+            f.process(us(i * 20833 + jitter), &TimingClock);
+        }
+        let bpm = f.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 0.5, "{}", bpm);
+
+        // after a gap the tempo is measured afresh
+        for i in 0..10 {
+            f.process(us(10_000_000 + i * 10_000), &TimingClock);
+        }
+        let bpm = f.bpm().unwrap();
+        assert!((bpm - 250.0).abs() < 0.01, "{}", bpm);
+    }
+
+    #[test]
+    fn test_transport() {
+        let mut f = ClockFollower::new();
+        let clocks = |f: &mut ClockFollower, n| {
+            for _ in 0..n {
+                f.process(us(0), &TimingClock);
+            }
+        };
+        clocks(&mut f, 5);
+        assert_eq!((f.transport(), f.position()), (Transport::Stopped, 0));
+
+        f.process(us(0), &Start);
+        clocks(&mut f, 13);
+        assert!(f.is_playing());
+        assert_eq!((f.position(), f.song_position()), (13, 2));
+
+        f.process(us(0), &Stop);
+        clocks(&mut f, 5);
+        f.process(us(0), &SongPositionPointer(8));
+        assert_eq!((f.transport(), f.position()), (Transport::Stopped, 48));
+
+        f.process(us(0), &Continue);
+        clocks(&mut f, 6);
+        assert_eq!(f.song_position(), 9);
+
+        f.process(us(0), &Start);
+        assert_eq!(f.position(), 0);
+    }
+
+    #[test]
+    fn test_generator() {
+        let start = Duration::from_secs(1);
+        let mut g = ClockGenerator::new(120.0, start);
+        g.start();
+        let messages = g.poll(start + us(20833));
+        assert_eq!(messages, vec![(start, Start), (start, TimingClock)]);
+        assert_eq!(g.next_time(), start + Duration::from_nanos(20_833_333));
+
+        // four hours at 120 BPM is exactly 691200 clocks
+        let four_hours = 4 * 60 * 60;
+        assert_eq!(g.clock_time(691_200), start + Duration::from_secs(four_hours));
+
+        // a tempo change applies from the next clock
+        g.poll(start + Duration::from_millis(499));
+        assert_eq!(g.position(), 24);
+        g.set_bpm(60.0);
+        assert_eq!(g.next_time(), start + Duration::from_millis(500));
+        assert_eq!(g.clock_time(48), start + Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_slowest_bpm() {
+        let g = ClockGenerator::new(0.001, Duration::from_secs(0));
+        assert_eq!(g.bpm(), 0.001);
+        assert_eq!(g.clock_time(24), Duration::from_secs(60_000));
+    }
+
+    #[test]
+    fn test_fastest_bpm() {
+        let g = ClockGenerator::new(MAX_BPM, Duration::from_secs(0));
+        assert_eq!(g.clock_time(24), Duration::from_nanos(600_000));
+    }
+
+    #[test]
+    #[should_panic(expected = "bpm must be between 0.001 and 100000")]
+    fn test_bpm_too_slow() {
+        ClockGenerator::new(0.0004, Duration::from_secs(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "bpm must be between 0.001 and 100000")]
+    fn test_bpm_too_fast() {
+        ClockGenerator::new(1e12, Duration::from_secs(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "bpm must be between 0.001 and 100000")]
+    fn test_bpm_infinite() {
+        let mut g = ClockGenerator::new(120.0, Duration::from_secs(0));
+        g.set_bpm(f64::INFINITY);
+    }
+
+    #[test]
+    fn test_swing() {
+        let mut g = ClockGenerator::new(120.0, Duration::from_secs(0));
+        g.set_swing(60);
+        assert_eq!(g.clock_time(6), Duration::from_millis(150));
+        assert_eq!(g.clock_time(12), Duration::from_millis(250));
+        assert_eq!(g.clock_time(18), Duration::from_millis(400));
+        assert_eq!(g.swing(), 60);
+    }
+
+    #[test]
+    fn test_generator_transport() {
+        let mut g = ClockGenerator::new(120.0, Duration::from_secs(0));
+        let mut f = ClockFollower::new();
+        let mut run = |g: &mut ClockGenerator, ms| {
+            let messages = g.poll(Duration::from_millis(ms));
+            for &(time, ref message) in &messages {
+                f.process(time, message);
+            }
+            (messages.into_iter().filter(|m| m.1 != TimingClock).map(|m| m.1).collect(),
+             f.clone())
+        };
+        g.start();
+        let (messages, f1): (Vec<Message>, _) = run(&mut g, 1000);
+        assert_eq!(messages, vec![Start]);
+        assert_eq!(f1.position(), 49);
+        assert_eq!(f1.bpm().map(|bpm| bpm.round()), Some(120.0));
+
+        g.relocate(4);
+        let (messages, f2) = run(&mut g, 1021);
+        assert_eq!(messages, vec![Stop, SongPositionPointer(4), Continue]);
+        assert_eq!((f2.position(), g.position()), (25, 25));
+
+        g.stop();
+        g.relocate(8);
+        g.resume();
+        let (messages, f3) = run(&mut g, 1500);
+        assert_eq!(messages, vec![Stop, SongPositionPointer(8), Continue]);
+        assert_eq!((f3.position(), f3.transport()), (48 + 23, Transport::Playing));
+    }
+}
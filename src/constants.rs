@@ -27,9 +27,63 @@ pub const STOP: u8 = 252;
 pub const ACTIVE_SENSING: u8 = 254;
 pub const SYSTEM_RESET: u8 = 255;
 
+pub const CC_BANK_SELECT_MSB: u8 = 0;
+pub const CC_MODULATION: u8 = 1;
+pub const CC_BREATH: u8 = 2;
+pub const CC_FOOT: u8 = 4;
+pub const CC_PORTAMENTO_TIME: u8 = 5;
+pub const CC_VOLUME: u8 = 7;
+pub const CC_BALANCE: u8 = 8;
+pub const CC_PAN: u8 = 10;
+pub const CC_EXPRESSION: u8 = 11;
+pub const CC_BANK_SELECT_LSB: u8 = 32;
+pub const CC_SUSTAIN: u8 = 64;
+pub const CC_PORTAMENTO: u8 = 65;
+pub const CC_SOSTENUTO: u8 = 66;
+pub const CC_SOFT_PEDAL: u8 = 67;
+pub const CC_LEGATO: u8 = 68;
+pub const CC_HOLD_2: u8 = 69;
+pub const CC_RESONANCE: u8 = 71;
+pub const CC_RELEASE: u8 = 72;
+pub const CC_ATTACK: u8 = 73;
+pub const CC_BRIGHTNESS: u8 = 74;
+pub const CC_REVERB: u8 = 91;
+pub const CC_TREMOLO: u8 = 92;
+pub const CC_CHORUS: u8 = 93;
+pub const CC_CELESTE: u8 = 94;
+pub const CC_PHASER: u8 = 95;
+pub const CC_DATA_INCREMENT: u8 = 96;
+pub const CC_DATA_DECREMENT: u8 = 97;
 pub const CC_RPN_MSB: u8 = 101;
 pub const CC_RPN_LSB: u8 = 100;
 pub const CC_NRPN_MSB: u8 = 99;
 pub const CC_NRPN_LSB: u8 = 98;
 pub const CC_DATA_ENTRY_MSB: u8 = 6;
 pub const CC_DATA_ENTRY_LSB: u8 = 38;
+pub const CC_ALL_SOUND_OFF: u8 = 120;
+pub const CC_RESET_ALL_CONTROLLERS: u8 = 121;
+pub const CC_LOCAL_CONTROL: u8 = 122;
+pub const CC_ALL_NOTES_OFF: u8 = 123;
+pub const CC_OMNI_OFF: u8 = 124;
+pub const CC_OMNI_ON: u8 = 125;
+pub const CC_MONO_ON: u8 = 126;
+pub const CC_POLY_ON: u8 = 127;
+
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+pub const RPN_PITCH_BEND_SENSITIVITY: u16 = 0;
+pub const RPN_FINE_TUNING: u16 = 1;
+pub const RPN_COARSE_TUNING: u16 = 2;
+pub const RPN_TUNING_PROGRAM: u16 = 3;
+pub const RPN_TUNING_BANK: u16 = 4;
+pub const RPN_NULL: u16 = 16383;
+
+/// The number of `TimingClock`s per quarter note
+pub const CLOCKS_PER_BEAT: u32 = 24;
+/// The number of `TimingClock`s per Midi beat (16th note), the unit of `SongPositionPointer`
+pub const CLOCKS_PER_MIDI_BEAT: u32 = 6;
+
+pub const UNIVERSAL_NON_REAL_TIME: u8 = 0x7E;
+pub const UNIVERSAL_REAL_TIME: u8 = 0x7F;
+/// The device ID of a Universal SysEx that is addressed to all devices
+pub const ALL_CALL: u8 = 0x7F;
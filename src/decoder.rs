@@ -0,0 +1,191 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use constants::*;
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::*;
+use utils::{from_status_byte, msb_lsb_to_u14};
+
+/// Decodes a stream of Midi bytes into `Message`s
+///
+/// Running status is supported, and realtime messages may be interleaved with any other message
+/// (including SysEx). Channel mode control changes are decoded to their `Message` (e.g.
+/// `AllNotesOff`), but RPNs and NRPNs are left as the individual `ControlChange`s. Bytes that do
+/// not form a `Message` that this crate can represent are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    running: Option<u8>,
+    data: Vec<u8>,
+    sysex: Option<Vec<u8>>
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// Decode a complete block of bytes
+    pub fn decode(bytes: &[u8]) -> Vec<Message> {
+        let mut decoder = Decoder::new();
+        bytes.iter().filter_map(|b| decoder.push(*b)).collect()
+    }
+
+    /// Add the next byte from the stream, returns a `Message` if one has been completed
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        match byte {
+            // System realtime, doesn't interrupt anything else
+            TIMING_CLOCK => Some(TimingClock),
+            START => Some(Start),
+            CONTINUE => Some(Continue),
+            STOP => Some(Stop),
+            ACTIVE_SENSING => Some(ActiveSensing),
+            SYSTEM_RESET => Some(SystemReset),
+            0xF9 | 0xFD => None,
+
+            SYSEX => {
+                self.running = None;
+                self.sysex = Some(Vec::new());
+                None
+            },
+            SYSEX_EOX => {
+                self.running = None;
+                self.sysex.take().and_then(|data| {
+                    Manufacturer::from_u7s(&data).map(|(m, data)| SysEx(m, data.to_vec()))
+                })
+            },
+            0x80..=0xEF => {
+                self.sysex = None;
+                self.running = Some(byte);
+                self.data.clear();
+                None
+            },
+            // quarter frames and song position pointer use running status for their data only,
+            // any other system common message cancels running status
+            0xF1..=0xF6 => {
+                self.sysex = None;
+                self.running = match byte {
+                    MTC_QUARTER_FRAME | SONG_POSITION_POINTER => Some(byte),
+                    _ => None
+                };
+                self.data.clear();
+                None
+            },
+            _ => {
+                if let Some(ref mut sysex) = self.sysex {
+                    sysex.push(byte);
+                    return None;
+                }
+                let status = self.running?;
+                self.data.push(byte);
+                let message = match (status, &self.data[..]) {
+                    (MTC_QUARTER_FRAME, &[data]) => {
+                        self.running = None;
+                        Some(MtcQuarterFrame(data >> 4, data & 0b1111))
+                    },
+                    (SONG_POSITION_POINTER, &[lsb, msb]) => {
+                        self.running = None;
+                        Some(SongPositionPointer(msb_lsb_to_u14(msb, lsb)))
+                    },
+                    (SONG_POSITION_POINTER, _) => None,
+                    _ => channel_message(status, &self.data)
+                };
+                if message.is_some() {
+                    self.data.clear();
+                }
+                message
+            }
+        }
+    }
+}
+
+// returns `None` if more data is needed
+fn channel_message(status: u8, data: &[u8]) -> Option<Message> {
+    let (status, ch) = from_status_byte(status);
+    match (status, data) {
+        (PROGRAM_CHANGE, &[no]) => Some(ProgramChange(ch, no)),
+        (CHANNEL_PRESSURE, &[val]) => Some(ChannelPressure(ch, val)),
+        (_, &[_]) => None,
+        (NOTE_OFF, &[no, vel]) => Some(NoteOff(ch, no, vel)),
+        (NOTE_ON, &[no, vel]) => Some(NoteOn(ch, no, vel)),
+        (POLYPHONIC_PRESSURE, &[no, val]) => Some(PolyphonicPressure(ch, no, val)),
+        (CONTROL_CHANGE, &[no, val]) => {
+            let message = match (no, val) {
+                (CC_ALL_SOUND_OFF, 0) => AllSoundOff(ch),
+                (CC_RESET_ALL_CONTROLLERS, 0) => ResetAllControllers(ch),
+                (CC_LOCAL_CONTROL, 0) => LocalControlOff(ch),
+                (CC_LOCAL_CONTROL, 127) => LocalControlOn(ch),
+                (CC_ALL_NOTES_OFF, 0) => AllNotesOff(ch),
+                _ => ControlChange(ch, no, val)
+            };
+            Some(message)
+        },
+        (PITCH_BEND, &[lsb, msb]) => Some(PitchBend(ch, msb_lsb_to_u14(msb, lsb))),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manufacturer::Manufacturer::*;
+    use to_raw_messages::ToRawMessages;
+    use types::Channel::*;
+
+    #[test]
+    fn test_decode() {
+        let bytes = [0x90, 0x3C, 0x64, 0x3C, 0x00,
+                     0xC1, 0x05, 0x06,
+                     0xB0, 0x7B, 0x00, 0x07, 0x5A,
+                     0xE2, 0x00, 0x40,
+                     0xF0, 0x41, 0x10, 0xF8, 0x42, 0xF7,
+                     0xFA, 0xFC];
+        assert_eq!(Decoder::decode(&bytes), vec![NoteOn(Ch1, 60, 100),
+                                                 NoteOn(Ch1, 60, 0),
+                                                 ProgramChange(Ch2, 5),
+                                                 ProgramChange(Ch2, 6),
+                                                 AllNotesOff(Ch1),
+                                                 ControlChange(Ch1, 7, 90),
+                                                 PitchBend(Ch3, 8192),
+                                                 TimingClock,
+                                                 SysEx(OneByte(0x41), vec![0x10, 0x42]),
+                                                 Start,
+                                                 Stop]);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        // data without a status, a SysEx interrupted by a note, running status cancelled by a
+        // SysEx and by a system common message, a note interrupted by a new status, and a song
+        // position pointer that does not run
+        let bytes = [0x3C, 0x64,
+                     0xF0, 0x41, 0x10, 0x90, 0x3C, 0x64,
+                     0xF0, 0x41, 0xF7, 0x3C, 0x64,
+                     0x90, 0xF3, 0x01, 0x3C,
+                     0x80, 0x3C, 0x90, 0x3C, 0x64,
+                     0xF2, 0x01, 0x02, 0x03, 0x04];
+        assert_eq!(Decoder::decode(&bytes), vec![NoteOn(Ch1, 60, 100),
+                                                 SysEx(OneByte(0x41), vec![]),
+                                                 NoteOn(Ch1, 60, 100),
+                                                 SongPositionPointer(257)]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let messages = vec![MtcQuarterFrame(5, 10), SongPositionPointer(1000),
+                            Start, TimingClock, Continue, Stop, ActiveSensing, SystemReset,
+                            AllSoundOff(Ch1), ResetAllControllers(Ch2), LocalControlOff(Ch3),
+                            LocalControlOn(Ch4), AllNotesOff(Ch5), NoteOff(Ch6, 1, 2),
+                            ProgramChange(Ch7, 3), ControlChange(Ch8, 4, 5),
+                            SysEx(ThreeByte(0, 1, 2), vec![3, 4]), NoteOn(Ch9, 6, 7),
+                            PitchBend(Ch10, 16383), PolyphonicPressure(Ch11, 8, 9),
+                            ChannelPressure(Ch16, 10)];
+        let bytes: Vec<u8> = messages.iter()
+            .flat_map(|m| m.to_raw_messages())
+            .flat_map(|r| r.to_bytes())
+            .collect();
+        assert_eq!(Decoder::decode(&bytes), messages);
+    }
+}
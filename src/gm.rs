@@ -0,0 +1,202 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::{Channel, U7, U14};
+use message::Message;
+use universal::{UniversalSysEx, NonRealTime, RealTime};
+use utils::{mask7, u14_to_msb_lsb};
+
+/// The channel used for percussion in General MIDI
+pub const GM_PERCUSSION_CHANNEL: Channel = Channel::Ch10;
+
+/// The sub-ID#2s of the General MIDI messages
+const GM1_SYSTEM_ON: U7 = 0x01;
+const GM_SYSTEM_OFF: U7 = 0x02;
+const GM2_SYSTEM_ON: U7 = 0x03;
+
+/// The sub-ID#2s of the Device Control messages
+const MASTER_VOLUME: U7 = 0x01;
+const MASTER_BALANCE: U7 = 0x02;
+const MASTER_FINE_TUNING: U7 = 0x03;
+const MASTER_COARSE_TUNING: U7 = 0x04;
+
+/// General MIDI System On, resets a device to its General MIDI defaults
+pub fn gm1_system_on(device: U7) -> Message {
+    gm_message(device, GM1_SYSTEM_ON)
+}
+
+/// General MIDI 2 System On, resets a device to its General MIDI 2 defaults
+pub fn gm2_system_on(device: U7) -> Message {
+    gm_message(device, GM2_SYSTEM_ON)
+}
+
+/// General MIDI System Off, returns a device to its own (non General MIDI) mode
+pub fn gm_system_off(device: U7) -> Message {
+    gm_message(device, GM_SYSTEM_OFF)
+}
+
+/// Master Volume, from 0 (silent) to 16383 (full)
+pub fn master_volume(device: U7, volume: U14) -> Message {
+    device_control(device, MASTER_VOLUME, volume)
+}
+
+/// Master Balance, from 0 (left) to 16383 (right), 8192 is centre
+pub fn master_balance(device: U7, balance: U14) -> Message {
+    device_control(device, MASTER_BALANCE, balance)
+}
+
+/// Master Fine Tuning, from 0 (-100 cents) to 16383 (+100 cents), 8192 is A440
+pub fn master_fine_tuning(device: U7, tuning: U14) -> Message {
+    device_control(device, MASTER_FINE_TUNING, tuning)
+}
+
+/// Master Coarse Tuning, in semitones from 0 (-64) to 127 (+63), 64 is A440
+pub fn master_coarse_tuning(device: U7, semitones: U7) -> Message {
+    UniversalSysEx::RealTime(device, RealTime::DeviceControl,
+                             vec![MASTER_COARSE_TUNING, 0, mask7(semitones)]).to_message()
+}
+
+fn gm_message(device: U7, sub_id: U7) -> Message {
+    UniversalSysEx::NonRealTime(device, NonRealTime::GeneralMidi, vec![sub_id]).to_message()
+}
+
+fn device_control(device: U7, sub_id: U7, value: U14) -> Message {
+    let (msb, lsb) = u14_to_msb_lsb(value);
+    UniversalSysEx::RealTime(device, RealTime::DeviceControl, vec![sub_id, lsb, msb]).to_message()
+}
+
+/// The name of a General MIDI program, as used with `ProgramChange` (0 is Acoustic Grand Piano)
+pub fn gm_program_name(program: U7) -> &'static str {
+    GM_PROGRAM_NAMES[mask7(program) as usize]
+}
+
+/// The name of the family of a General MIDI program, each family is 8 programs
+pub fn gm_family_name(program: U7) -> &'static str {
+    GM_FAMILY_NAMES[mask7(program) as usize / 8]
+}
+
+/// The name of a General MIDI percussion sound, as played by a note on `GM_PERCUSSION_CHANNEL`,
+/// `None` if the note has no sound
+pub fn gm_percussion_name(note: U7) -> Option<&'static str> {
+    let first = GM_FIRST_PERCUSSION as usize;
+    (note as usize).checked_sub(first).and_then(|i| GM_PERCUSSION_NAMES.get(i)).cloned()
+}
+
+pub const GM_FAMILY_NAMES: [&str; 16] = [
+    "Piano", "Chromatic Percussion", "Organ", "Guitar",
+    "Bass", "Strings", "Ensemble", "Brass",
+    "Reed", "Pipe", "Synth Lead", "Synth Pad",
+    "Synth Effects", "Ethnic", "Percussive", "Sound Effects"
+];
+
+pub const GM_PROGRAM_NAMES: [&str; 128] = [
+    // Piano
+    "Acoustic Grand Piano", "Bright Acoustic Piano", "Electric Grand Piano", "Honky-tonk Piano",
+    "Electric Piano 1", "Electric Piano 2", "Harpsichord", "Clavi",
+    // Chromatic Percussion
+    "Celesta", "Glockenspiel", "Music Box", "Vibraphone",
+    "Marimba", "Xylophone", "Tubular Bells", "Dulcimer",
+    // Organ
+    "Drawbar Organ", "Percussive Organ", "Rock Organ", "Church Organ",
+    "Reed Organ", "Accordion", "Harmonica", "Tango Accordion",
+    // Guitar
+    "Acoustic Guitar (nylon)", "Acoustic Guitar (steel)", "Electric Guitar (jazz)",
+    "Electric Guitar (clean)", "Electric Guitar (muted)", "Overdriven Guitar",
+    "Distortion Guitar", "Guitar Harmonics",
+    // Bass
+    "Acoustic Bass", "Electric Bass (finger)", "Electric Bass (pick)", "Fretless Bass",
+    "Slap Bass 1", "Slap Bass 2", "Synth Bass 1", "Synth Bass 2",
+    // Strings
+    "Violin", "Viola", "Cello", "Contrabass",
+    "Tremolo Strings", "Pizzicato Strings", "Orchestral Harp", "Timpani",
+    // Ensemble
+    "String Ensemble 1", "String Ensemble 2", "SynthStrings 1", "SynthStrings 2",
+    "Choir Aahs", "Voice Oohs", "Synth Voice", "Orchestra Hit",
+    // Brass
+    "Trumpet", "Trombone", "Tuba", "Muted Trumpet",
+    "French Horn", "Brass Section", "SynthBrass 1", "SynthBrass 2",
+    // Reed
+    "Soprano Sax", "Alto Sax", "Tenor Sax", "Baritone Sax",
+    "Oboe", "English Horn", "Bassoon", "Clarinet",
+    // Pipe
+    "Piccolo", "Flute", "Recorder", "Pan Flute",
+    "Blown Bottle", "Shakuhachi", "Whistle", "Ocarina",
+    // Synth Lead
+    "Lead 1 (square)", "Lead 2 (sawtooth)", "Lead 3 (calliope)", "Lead 4 (chiff)",
+    "Lead 5 (charang)", "Lead 6 (voice)", "Lead 7 (fifths)", "Lead 8 (bass + lead)",
+    // Synth Pad
+    "Pad 1 (new age)", "Pad 2 (warm)", "Pad 3 (polysynth)", "Pad 4 (choir)",
+    "Pad 5 (bowed)", "Pad 6 (metallic)", "Pad 7 (halo)", "Pad 8 (sweep)",
+    // Synth Effects
+    "FX 1 (rain)", "FX 2 (soundtrack)", "FX 3 (crystal)", "FX 4 (atmosphere)",
+    "FX 5 (brightness)", "FX 6 (goblins)", "FX 7 (echoes)", "FX 8 (sci-fi)",
+    // Ethnic
+    "Sitar", "Banjo", "Shamisen", "Koto",
+    "Kalimba", "Bag pipe", "Fiddle", "Shanai",
+    // Percussive
+    "Tinkle Bell", "Agogo", "Steel Drums", "Woodblock",
+    "Taiko Drum", "Melodic Tom", "Synth Drum", "Reverse Cymbal",
+    // Sound Effects
+    "Guitar Fret Noise", "Breath Noise", "Seashore", "Bird Tweet",
+    "Telephone Ring", "Helicopter", "Applause", "Gunshot"
+];
+
+/// The note of the first General MIDI percussion sound
+pub const GM_FIRST_PERCUSSION: U7 = 35;
+
+/// The General MIDI percussion sounds, starting at `GM_FIRST_PERCUSSION`
+pub const GM_PERCUSSION_NAMES: [&str; 47] = [
+    "Acoustic Bass Drum", "Bass Drum 1", "Side Stick", "Acoustic Snare", "Hand Clap",
+    "Electric Snare", "Low Floor Tom", "Closed Hi Hat", "High Floor Tom", "Pedal Hi-Hat",
+    "Low Tom", "Open Hi-Hat", "Low-Mid Tom", "Hi-Mid Tom", "Crash Cymbal 1",
+    "High Tom", "Ride Cymbal 1", "Chinese Cymbal", "Ride Bell", "Tambourine",
+    "Splash Cymbal", "Cowbell", "Crash Cymbal 2", "Vibraslap", "Ride Cymbal 2",
+    "Hi Bongo", "Low Bongo", "Mute Hi Conga", "Open Hi Conga", "Low Conga",
+    "High Timbale", "Low Timbale", "High Agogo", "Low Agogo", "Cabasa",
+    "Maracas", "Short Whistle", "Long Whistle", "Short Guiro", "Long Guiro",
+    "Claves", "Hi Wood Block", "Low Wood Block", "Mute Cuica", "Open Cuica",
+    "Mute Triangle", "Open Triangle"
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::ALL_CALL;
+    use manufacturer::Manufacturer::*;
+    use Message::SysEx;
+
+    #[test]
+    fn test_system_messages() {
+        assert_eq!(gm1_system_on(ALL_CALL), SysEx(OneByte(0x7E), vec![0x7F, 0x09, 0x01]));
+        assert_eq!(gm_system_off(0x10), SysEx(OneByte(0x7E), vec![0x10, 0x09, 0x02]));
+        assert_eq!(gm2_system_on(ALL_CALL), SysEx(OneByte(0x7E), vec![0x7F, 0x09, 0x03]));
+    }
+
+    #[test]
+    fn test_device_control() {
+        assert_eq!(master_volume(ALL_CALL, 16383),
+                   SysEx(OneByte(0x7F), vec![0x7F, 0x04, 0x01, 0x7F, 0x7F]));
+        assert_eq!(master_balance(ALL_CALL, 8192),
+                   SysEx(OneByte(0x7F), vec![0x7F, 0x04, 0x02, 0x00, 0x40]));
+        assert_eq!(master_fine_tuning(ALL_CALL, 8193),
+                   SysEx(OneByte(0x7F), vec![0x7F, 0x04, 0x03, 0x01, 0x40]));
+        assert_eq!(master_coarse_tuning(ALL_CALL, 62),
+                   SysEx(OneByte(0x7F), vec![0x7F, 0x04, 0x04, 0x00, 0x3E]));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(gm_program_name(0), "Acoustic Grand Piano");
+        assert_eq!(gm_program_name(40), "Violin");
+        assert_eq!(gm_program_name(127), "Gunshot");
+        assert_eq!(gm_family_name(40), "Strings");
+        assert_eq!(gm_family_name(127), "Sound Effects");
+        assert_eq!(gm_percussion_name(34), None);
+        assert_eq!(gm_percussion_name(35), Some("Acoustic Bass Drum"));
+        assert_eq!(gm_percussion_name(42), Some("Closed Hi Hat"));
+        assert_eq!(gm_percussion_name(81), Some("Open Triangle"));
+        assert_eq!(gm_percussion_name(82), None);
+    }
+}
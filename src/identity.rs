@@ -0,0 +1,164 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::{U7, U14};
+use manufacturer::Manufacturer;
+use message::Message;
+use universal::{UniversalSysEx, NonRealTime};
+use utils::{msb_lsb_to_u14, u14_to_msb_lsb};
+
+/// The sub-ID#2s of the General Information messages
+const IDENTITY_REQUEST: U7 = 0x01;
+const IDENTITY_REPLY: U7 = 0x02;
+
+/// The Identity Request message, asks `device` (or `ALL_CALL` for all devices) to send an
+/// Identity Reply
+pub fn identity_request(device: U7) -> Message {
+    UniversalSysEx::NonRealTime(device, NonRealTime::GeneralInformation, vec![IDENTITY_REQUEST])
+        .to_message()
+}
+
+/// The reply to an Identity Request, describing the device that sent it
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IdentityReply {
+    /// The device ID of the device
+    pub device: U7,
+    pub manufacturer: Manufacturer,
+    /// The device family code, as defined by the manufacturer
+    pub family: U14,
+    /// The model within the family, as defined by the manufacturer
+    pub member: U14,
+    /// The software revision, in the format defined by the manufacturer
+    pub revision: [U7; 4]
+}
+
+impl IdentityReply {
+    /// Read an Identity Reply message, returns `None` if the message is not one
+    ///
+    /// Some devices send extra bytes after the revision, these are ignored.
+    pub fn from_message(message: &Message) -> Option<IdentityReply> {
+        let (device, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, NonRealTime::GeneralInformation, data) => {
+                (device, data)
+            },
+            _ => return None
+        };
+        let (manufacturer, rest) = match data.split_first() {
+            Some((&IDENTITY_REPLY, rest)) => Manufacturer::from_u7s(rest)?,
+            _ => return None
+        };
+        match *rest {
+            [f1, f2, m1, m2, r1, r2, r3, r4, ..] => Some(IdentityReply {
+                device,
+                manufacturer,
+                family: msb_lsb_to_u14(f2, f1),
+                member: msb_lsb_to_u14(m2, m1),
+                revision: [r1, r2, r3, r4]
+            }),
+            _ => None
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        let (family_msb, family_lsb) = u14_to_msb_lsb(self.family);
+        let (member_msb, member_lsb) = u14_to_msb_lsb(self.member);
+        let mut data = vec![IDENTITY_REPLY];
+        data.extend(self.manufacturer.to_u7s());
+        data.extend_from_slice(&[family_lsb, family_msb, member_lsb, member_msb]);
+        data.extend_from_slice(&self.revision);
+        UniversalSysEx::NonRealTime(self.device, NonRealTime::GeneralInformation, data)
+            .to_message()
+    }
+}
+
+/// Recognises a device from its Identity Reply
+///
+/// A `family` or `member` of `None` matches any value, so a matcher can be as specific as needed,
+/// e.g. any device from a manufacturer, or a single model.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IdentityMatcher {
+    pub manufacturer: Manufacturer,
+    pub family: Option<U14>,
+    pub member: Option<U14>
+}
+
+impl IdentityMatcher {
+    pub fn new(manufacturer: Manufacturer, family: Option<U14>, member: Option<U14>)
+               -> IdentityMatcher {
+        IdentityMatcher { manufacturer, family, member }
+    }
+
+    pub fn matches(&self, reply: &IdentityReply) -> bool {
+        self.manufacturer == reply.manufacturer &&
+            self.family.is_none_or(|f| f == reply.family) &&
+            self.member.is_none_or(|m| m == reply.member)
+    }
+
+    /// Find the most specific matcher for `reply` from a list of `(value, matcher)` pairs, returns
+    /// its value
+    pub fn find<'a, T>(matchers: &'a [(T, IdentityMatcher)], reply: &IdentityReply)
+                       -> Option<&'a T> {
+        matchers.iter()
+            .filter(|m| m.1.matches(reply))
+            .max_by_key(|m| (m.1.family.is_some(), m.1.member.is_some()))
+            .map(|m| &m.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::ALL_CALL;
+    use manufacturer::Manufacturer::*;
+    use Message::SysEx;
+
+    fn reply() -> IdentityReply {
+        IdentityReply {
+            device: 0x10,
+            manufacturer: OneByte(0x41),
+            family: 0x0142,
+            member: 3,
+            revision: [0, 1, 0, 0]
+        }
+    }
+
+    #[test]
+    fn test_request() {
+        assert_eq!(identity_request(ALL_CALL), SysEx(OneByte(0x7E), vec![0x7F, 0x06, 0x01]));
+    }
+
+    #[test]
+    fn test_reply() {
+        let message = SysEx(OneByte(0x7E),
+                            vec![0x10, 0x06, 0x02, 0x41, 0x42, 0x02, 0x03, 0x00, 0, 1, 0, 0]);
+        assert_eq!(IdentityReply::from_message(&message), Some(reply()));
+        assert_eq!(reply().to_message(), message);
+
+        let three_byte = IdentityReply { manufacturer: ThreeByte(0, 0x20, 0x33), ..reply() };
+        assert_eq!(IdentityReply::from_message(&three_byte.to_message()), Some(three_byte));
+
+        assert_eq!(IdentityReply::from_message(&identity_request(0x10)), None);
+        let short = SysEx(OneByte(0x7E), vec![0x10, 0x06, 0x02, 0x41, 0x42, 0x02, 0x03]);
+        assert_eq!(IdentityReply::from_message(&short), None);
+        let trailing = SysEx(OneByte(0x7E), vec![0x10, 0x06, 0x02, 0x41, 0x42, 0x02, 0x03, 0x00,
+                                                 0, 1, 0, 0, 0x7F]);
+        assert_eq!(IdentityReply::from_message(&trailing), Some(reply()));
+    }
+
+    #[test]
+    fn test_matcher() {
+        let matchers = [("roland", IdentityMatcher::new(OneByte(0x41), None, None)),
+                        ("family", IdentityMatcher::new(OneByte(0x41), Some(0x0142), None)),
+                        ("model", IdentityMatcher::new(OneByte(0x41), Some(0x0142), Some(3))),
+                        ("other", IdentityMatcher::new(OneByte(0x43), None, None))];
+        assert_eq!(IdentityMatcher::find(&matchers, &reply()), Some(&"model"));
+        let other_model = IdentityReply { member: 4, ..reply() };
+        assert_eq!(IdentityMatcher::find(&matchers, &other_model), Some(&"family"));
+        let other_family = IdentityReply { family: 1, ..reply() };
+        assert_eq!(IdentityMatcher::find(&matchers, &other_family), Some(&"roland"));
+        let korg = IdentityReply { manufacturer: OneByte(0x42), ..reply() };
+        assert_eq!(IdentityMatcher::find(&matchers, &korg), None);
+    }
+}
@@ -6,6 +6,11 @@
 //! Midi types and traits for Rust
 
 extern crate num;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub use types::{Channel, U7, U14};
 pub use Channel::{Ch1,  Ch2,  Ch3,  Ch4,  Ch5,  Ch6,  Ch7,  Ch8,
@@ -13,13 +18,46 @@ pub use Channel::{Ch1,  Ch2,  Ch3,  Ch4,  Ch5,  Ch6,  Ch7,  Ch8,
 pub use raw_message::RawMessage;
 pub use RawMessage::{Status, StatusData, StatusDataData, Raw};
 pub use message::Message;
-pub use Message::{Start, TimingClock, Continue, Stop, ActiveSensing, SystemReset,
+pub use Message::{MtcQuarterFrame, SongPositionPointer,
+                  Start, TimingClock, Continue, Stop, ActiveSensing, SystemReset,
                   AllSoundOff, ResetAllControllers, LocalControlOff, LocalControlOn,
                   AllNotesOff, NoteOff,
                   ProgramChange, ControlChange, RPN7, RPN14, NRPN7, NRPN14,
                   SysEx, NoteOn, PitchBend, PolyphonicPressure, ChannelPressure};
 pub use manufacturer::Manufacturer;
+pub use note::{Note, ParseNoteError};
+pub use message_text::ParseMessageError;
 pub use to_raw_messages::ToRawMessages;
+pub use track::{Event, Track};
+pub use note_span::{NoteSpan, Overlap, SpanCollector, note_spans, spans_to_track};
+pub use rmid::{Rmid, RmidInfo, RmidError, RiffChunk};
+pub use syx::{SyxError, read_syx, write_syx};
+pub use smf::{Smf, SmfTrack, SmfEvent, SmfEventKind, SmfChunk, SmfError, MetaEvent, Division};
+pub use smf_text::{TimeFormat, ParseSmfError};
+pub use decoder::Decoder;
+pub use state::{ChannelState, MidiState};
+pub use note_guard::NoteGuard;
+pub use sensing::{SensingWatchdog, SensingSender};
+pub use clock::{ClockFollower, ClockGenerator, Transport};
+pub use timecode::{FrameRate, Timecode, Direction, MtcDecoder, MtcGenerator};
+pub use universal::{UniversalSysEx, NonRealTime, RealTime};
+pub use identity::{IdentityReply, IdentityMatcher, identity_request};
+pub use gm::{gm1_system_on, gm2_system_on, gm_system_off, master_volume, master_balance,
+             master_fine_tuning, master_coarse_tuning, gm_program_name, gm_family_name,
+             gm_percussion_name, GM_PERCUSSION_CHANNEL, GM_FAMILY_NAMES, GM_PROGRAM_NAMES,
+             GM_FIRST_PERCUSSION, GM_PERCUSSION_NAMES};
+pub use roland::{roland_checksum, roland_dt1, roland_rq1, roland_checksum_ok, gs_reset, ROLAND,
+                 GS_MODEL, ROLAND_DEFAULT_DEVICE};
+pub use yamaha::{xg_parameter_change, xg_system_on, YAMAHA, XG_MODEL};
+pub use mmc::{MmcCommand, MmcResponse, mmc_message, mmc_response_message,
+              MMC_SELECTED_TIME_CODE};
+pub use msc::{MscCommand, MscCommandFormat, MscCue};
+pub use tuning::{Cents, Hz, TuningPitch, SingleNoteTuning, BulkTuningDump, ScaleOffsets,
+                 ScaleOctaveTuning, bulk_tuning_dump_request, tuning_program_select,
+                 tuning_bank_select};
+pub use scala::{ScalaScale, KeyboardMapping, TuningTable, ParseScalaError};
+pub use sds::{SampleDumpHeader, SampleDataPacket, LoopType, Handshake, SdsSender, SdsReceiver,
+              sample_dump_request, SDS_PACKET_LENGTH, SDS_MAX_VALUE};
 
 pub mod constants;
 pub mod utils;
@@ -28,5 +66,31 @@ mod types;
 mod raw_message;
 mod message;
 mod manufacturer;
+mod note;
+mod message_text;
 mod to_raw_messages;
+mod track;
+mod note_span;
+mod rmid;
+mod syx;
+mod smf;
+mod smf_text;
+mod decoder;
+mod state;
+mod note_guard;
+mod sensing;
+mod clock;
+mod timecode;
+mod universal;
+mod identity;
+mod gm;
+mod roland;
+mod yamaha;
+mod mmc;
+mod msc;
+mod tuning;
+mod scala;
+mod sds;
+#[cfg(feature = "serde")]
+mod serde_impls;
 
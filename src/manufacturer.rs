@@ -14,9 +14,23 @@ pub enum Manufacturer {
 
 impl Manufacturer {
     pub fn to_u7s(&self) -> Vec<U7> {
-        match self {
-            &Manufacturer::OneByte(b) => vec!(mask7(b)),
-            &Manufacturer::ThreeByte(b1, b2, b3) => vec!(mask7(b1), mask7(b2), mask7(b3))
+        match *self {
+            Manufacturer::OneByte(b) => vec!(mask7(b)),
+            Manufacturer::ThreeByte(b1, b2, b3) => vec!(mask7(b1), mask7(b2), mask7(b3))
+        }
+    }
+
+    /// Parse the manufacturer from the start of some SysEx data, returns the manufacturer and the
+    /// remaining data
+    ///
+    /// A leading `0` indicates a three byte manufacturer ID. Returns `None` if there is not enough
+    /// data.
+    pub fn from_u7s(data: &[U7]) -> Option<(Manufacturer, &[U7])> {
+        match data {
+            [0, b2, b3, rest @ ..] => Some((Manufacturer::ThreeByte(0, *b2, *b3), rest)),
+            [0, ..] => None,
+            [b, rest @ ..] => Some((Manufacturer::OneByte(*b), rest)),
+            [] => None
         }
     }
 }
@@ -13,6 +13,19 @@ use manufacturer::Manufacturer;
 /// `ControlChange` and `ProgramChange` before `NoteOn`, etc, etc
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Message {
+    // System common
+    // -------------
+
+    /// MIDI Time Code Quarter Frame. One of the 8 pieces of a SMPTE time, see `MtcDecoder`.
+    /// The first argument is the piece (0-7).
+    /// The second argument is the 4 bit value of the piece.
+    MtcQuarterFrame(U7, U7),
+
+    /// Song Position Pointer. The position in the song to play from when a `Continue` is
+    /// received, counted in Midi beats (16th notes) from the start of the song. Each Midi beat is
+    /// 6 Timing Clocks.
+    SongPositionPointer(U14),
+
     // System realtime
     // ---------------
 
@@ -35,6 +48,8 @@ pub enum Message {
     /// will expect to receive another Active Sensing message each 300ms (max), and if it does not
     /// then it will assume that the connection has been terminated. At termination, the receiver
     /// will turn off all voices and return to normal (non- active sensing) operation.
+    ///
+    /// See `SensingWatchdog` and `SensingSender`.
     ActiveSensing,
 
     /// Reset. Reset all receivers in the system to power-up status. This should be used sparingly,
@@ -132,3 +147,18 @@ pub enum Message {
     ChannelPressure(Channel, U7)
 }
 
+impl Message {
+    /// The channel of a channel mode or channel voice message, `None` for system messages
+    pub fn channel(&self) -> Option<Channel> {
+        use self::Message::*;
+        match *self {
+            AllSoundOff(ch) | ResetAllControllers(ch) | LocalControlOff(ch) |
+            LocalControlOn(ch) | AllNotesOff(ch) | NoteOff(ch, _, _) | ProgramChange(ch, _) |
+            ControlChange(ch, _, _) | RPN7(ch, _, _) | RPN14(ch, _, _) | NRPN7(ch, _, _) |
+            NRPN14(ch, _, _) | NoteOn(ch, _, _) | PitchBend(ch, _) |
+            PolyphonicPressure(ch, _, _) | ChannelPressure(ch, _) => Some(ch),
+            MtcQuarterFrame(..) | SongPositionPointer(_) | Start | TimingClock | Continue | Stop |
+            ActiveSensing | SystemReset | SysEx(..) => None
+        }
+    }
+}
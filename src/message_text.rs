@@ -0,0 +1,399 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::str::{FromStr, SplitWhitespace};
+
+use num::FromPrimitive;
+
+use constants::*;
+use types::{Channel, U7};
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::*;
+use note::Note;
+
+// names used for the common controllers, any others are written as numbers
+const CONTROLLER_NAMES: [(U7, &str); 33] = [
+    (CC_BANK_SELECT_MSB, "bank_select"),
+    (CC_MODULATION, "modulation"),
+    (CC_BREATH, "breath"),
+    (CC_FOOT, "foot"),
+    (CC_PORTAMENTO_TIME, "portamento_time"),
+    (CC_DATA_ENTRY_MSB, "data_entry"),
+    (CC_VOLUME, "volume"),
+    (CC_BALANCE, "balance"),
+    (CC_PAN, "pan"),
+    (CC_EXPRESSION, "expression"),
+    (CC_BANK_SELECT_LSB, "bank_select_lsb"),
+    (CC_DATA_ENTRY_LSB, "data_entry_lsb"),
+    (CC_SUSTAIN, "sustain"),
+    (CC_PORTAMENTO, "portamento"),
+    (CC_SOSTENUTO, "sostenuto"),
+    (CC_SOFT_PEDAL, "soft_pedal"),
+    (CC_LEGATO, "legato"),
+    (CC_HOLD_2, "hold_2"),
+    (CC_RESONANCE, "resonance"),
+    (CC_RELEASE, "release"),
+    (CC_ATTACK, "attack"),
+    (CC_BRIGHTNESS, "brightness"),
+    (CC_REVERB, "reverb"),
+    (CC_TREMOLO, "tremolo"),
+    (CC_CHORUS, "chorus"),
+    (CC_CELESTE, "celeste"),
+    (CC_PHASER, "phaser"),
+    (CC_DATA_INCREMENT, "data_increment"),
+    (CC_DATA_DECREMENT, "data_decrement"),
+    (CC_NRPN_LSB, "nrpn_lsb"),
+    (CC_NRPN_MSB, "nrpn_msb"),
+    (CC_RPN_LSB, "rpn_lsb"),
+    (CC_RPN_MSB, "rpn_msb")
+];
+
+/// Formats a `Message` as text, e.g. `note_on ch1 C4 vel=100`, `cc ch2 volume 90`,
+/// `nrpn14 ch1 1000 1001` or `sysex 0x41 [10 20 30]`
+///
+/// The text can be parsed back to an identical `Message` with `FromStr`. Notes are written with
+/// their names (see `Note`), controllers with a name if they have a common one, and SysEx data is
+/// written in hex.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // System common
+            MtcQuarterFrame(piece, value) => write!(f, "mtc_quarter_frame {} {}", piece, value),
+            SongPositionPointer(position) => write!(f, "song_position {}", position),
+
+            // System realtime
+            Start => f.write_str("start"),
+            TimingClock => f.write_str("timing_clock"),
+            Continue => f.write_str("continue"),
+            Stop => f.write_str("stop"),
+            ActiveSensing => f.write_str("active_sensing"),
+            SystemReset => f.write_str("system_reset"),
+
+            // Channel mode
+            AllSoundOff(ch) => write!(f, "all_sound_off {}", ChannelText(ch)),
+            ResetAllControllers(ch) => write!(f, "reset_all_controllers {}", ChannelText(ch)),
+            LocalControlOff(ch) => write!(f, "local_control_off {}", ChannelText(ch)),
+            LocalControlOn(ch) => write!(f, "local_control_on {}", ChannelText(ch)),
+            AllNotesOff(ch) => write!(f, "all_notes_off {}", ChannelText(ch)),
+
+            // Channel voice
+            NoteOff(ch, no, vel) => {
+                write!(f, "note_off {} {} vel={}", ChannelText(ch), NoteText(no), vel)
+            },
+            ProgramChange(ch, no) => write!(f, "pc {} {}", ChannelText(ch), no),
+            ControlChange(ch, no, val) => {
+                write!(f, "cc {} {} {}", ChannelText(ch), ControllerText(no), val)
+            },
+            RPN7(ch, rpn, val) => write!(f, "rpn7 {} {} {}", ChannelText(ch), rpn, val),
+            RPN14(ch, rpn, val) => write!(f, "rpn14 {} {} {}", ChannelText(ch), rpn, val),
+            NRPN7(ch, nrpn, val) => write!(f, "nrpn7 {} {} {}", ChannelText(ch), nrpn, val),
+            NRPN14(ch, nrpn, val) => write!(f, "nrpn14 {} {} {}", ChannelText(ch), nrpn, val),
+            SysEx(manufacturer, ref data) => {
+                match manufacturer {
+                    Manufacturer::OneByte(b) => write!(f, "sysex 0x{:02X} [", b)?,
+                    Manufacturer::ThreeByte(b1, b2, b3) => {
+                        write!(f, "sysex 0x{:02X} 0x{:02X} 0x{:02X} [", b1, b2, b3)?
+                    }
+                }
+                for (i, b) in data.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{:02X}", b)?;
+                }
+                f.write_str("]")
+            },
+            NoteOn(ch, no, vel) => {
+                write!(f, "note_on {} {} vel={}", ChannelText(ch), NoteText(no), vel)
+            },
+            PitchBend(ch, bend) => write!(f, "pitch_bend {} {}", ChannelText(ch), bend),
+            PolyphonicPressure(ch, no, val) => {
+                write!(f, "poly_pressure {} {} {}", ChannelText(ch), NoteText(no), val)
+            },
+            ChannelPressure(ch, val) => write!(f, "channel_pressure {} {}", ChannelText(ch), val)
+        }
+    }
+}
+
+/// Errors from parsing a `Message` from text
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseMessageError {
+    /// The text was empty
+    Empty,
+    /// The message name was not recognised
+    UnknownMessage(String),
+    /// The message needs more arguments
+    MissingArgument,
+    /// An argument could not be parsed
+    InvalidArgument(String),
+    /// There were more arguments than the message needs
+    UnexpectedArgument(String)
+}
+
+impl fmt::Display for ParseMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseMessageError::Empty => f.write_str("empty message"),
+            ParseMessageError::UnknownMessage(ref s) => write!(f, "unknown message '{}'", s),
+            ParseMessageError::MissingArgument => f.write_str("missing argument"),
+            ParseMessageError::InvalidArgument(ref s) => write!(f, "invalid argument '{}'", s),
+            ParseMessageError::UnexpectedArgument(ref s) => {
+                write!(f, "unexpected argument '{}'", s)
+            }
+        }
+    }
+}
+
+impl Error for ParseMessageError {}
+
+impl FromStr for Message {
+    type Err = ParseMessageError;
+
+    fn from_str(s: &str) -> Result<Message, ParseMessageError> {
+        let s = s.trim();
+        let (name, rest) = match s.find(char::is_whitespace) {
+            Some(i) => (&s[..i], &s[i..]),
+            None => (s, "")
+        };
+        let mut args = Args { iter: rest.split_whitespace() };
+        let message = match name {
+            "" => return Err(ParseMessageError::Empty),
+
+            // System common
+            "mtc_quarter_frame" => MtcQuarterFrame(args.number()?, args.number()?),
+            "song_position" => SongPositionPointer(args.number()?),
+
+            // System realtime
+            "start" => Start,
+            "timing_clock" => TimingClock,
+            "continue" => Continue,
+            "stop" => Stop,
+            "active_sensing" => ActiveSensing,
+            "system_reset" => SystemReset,
+
+            // Channel mode
+            "all_sound_off" => AllSoundOff(args.channel()?),
+            "reset_all_controllers" => ResetAllControllers(args.channel()?),
+            "local_control_off" => LocalControlOff(args.channel()?),
+            "local_control_on" => LocalControlOn(args.channel()?),
+            "all_notes_off" => AllNotesOff(args.channel()?),
+
+            // Channel voice
+            "note_off" => NoteOff(args.channel()?, args.note()?, args.velocity()?),
+            "pc" => ProgramChange(args.channel()?, args.number()?),
+            "cc" => ControlChange(args.channel()?, args.controller()?, args.number()?),
+            "rpn7" => RPN7(args.channel()?, args.number()?, args.number()?),
+            "rpn14" => RPN14(args.channel()?, args.number()?, args.number()?),
+            "nrpn7" => NRPN7(args.channel()?, args.number()?, args.number()?),
+            "nrpn14" => NRPN14(args.channel()?, args.number()?, args.number()?),
+            "sysex" => return parse_sysex(rest),
+            "note_on" => NoteOn(args.channel()?, args.note()?, args.velocity()?),
+            "pitch_bend" => PitchBend(args.channel()?, args.number()?),
+            "poly_pressure" => PolyphonicPressure(args.channel()?, args.note()?, args.number()?),
+            "channel_pressure" => ChannelPressure(args.channel()?, args.number()?),
+
+            _ => return Err(ParseMessageError::UnknownMessage(name.to_string()))
+        };
+        args.finish(message)
+    }
+}
+
+struct ChannelText(Channel);
+
+impl fmt::Display for ChannelText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ch{}", self.0 as u8 + 1)
+    }
+}
+
+// out of range notes are written as numbers so that they still round trip
+struct NoteText(U7);
+
+impl fmt::Display for NoteText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 < 128 {
+            write!(f, "{}", Note(self.0))
+        }
+        else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+struct ControllerText(U7);
+
+impl fmt::Display for ControllerText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match CONTROLLER_NAMES.iter().find(|c| c.0 == self.0) {
+            Some(&(_, name)) => f.write_str(name),
+            None => write!(f, "{}", self.0)
+        }
+    }
+}
+
+struct Args<'a> {
+    iter: SplitWhitespace<'a>
+}
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> Result<&'a str, ParseMessageError> {
+        self.iter.next().ok_or(ParseMessageError::MissingArgument)
+    }
+
+    fn channel(&mut self) -> Result<Channel, ParseMessageError> {
+        let arg = self.next()?;
+        arg.strip_prefix("ch")
+            .and_then(|n| n.parse::<u8>().ok())
+            .and_then(|n| n.checked_sub(1))
+            .and_then(Channel::from_u8)
+            .ok_or_else(|| invalid(arg))
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, ParseMessageError> {
+        let arg = self.next()?;
+        arg.parse().map_err(|_| invalid(arg))
+    }
+
+    fn note(&mut self) -> Result<U7, ParseMessageError> {
+        let arg = self.next()?;
+        arg.parse::<U7>()
+            .or_else(|_| arg.parse::<Note>().map(|n| n.0))
+            .map_err(|_| invalid(arg))
+    }
+
+    fn velocity(&mut self) -> Result<U7, ParseMessageError> {
+        let arg = self.next()?;
+        arg.strip_prefix("vel=").unwrap_or(arg).parse().map_err(|_| invalid(arg))
+    }
+
+    fn controller(&mut self) -> Result<U7, ParseMessageError> {
+        let arg = self.next()?;
+        arg.parse::<U7>().ok()
+            .or_else(|| CONTROLLER_NAMES.iter().find(|c| c.1 == arg).map(|c| c.0))
+            .ok_or_else(|| invalid(arg))
+    }
+
+    fn finish(mut self, message: Message) -> Result<Message, ParseMessageError> {
+        match self.iter.next() {
+            Some(arg) => Err(ParseMessageError::UnexpectedArgument(arg.to_string())),
+            None => Ok(message)
+        }
+    }
+}
+
+fn invalid(arg: &str) -> ParseMessageError {
+    ParseMessageError::InvalidArgument(arg.to_string())
+}
+
+fn parse_hex(arg: &str) -> Result<u8, ParseMessageError> {
+    u8::from_str_radix(arg, 16).map_err(|_| invalid(arg))
+}
+
+// e.g. " 0x41 [10 20 30]" or " 0x00 0x20 0x33 []"
+fn parse_sysex(s: &str) -> Result<Message, ParseMessageError> {
+    let open = s.find('[').ok_or(ParseMessageError::MissingArgument)?;
+    let close = s.find(']').ok_or(ParseMessageError::MissingArgument)?;
+    if close < open {
+        return Err(invalid(&s[close..]));
+    }
+    let ids = s[..open].split_whitespace()
+        .map(|arg| arg.strip_prefix("0x").ok_or_else(|| invalid(arg)).and_then(parse_hex))
+        .collect::<Result<Vec<u8>, _>>()?;
+    let manufacturer = match ids[..] {
+        [b] => Manufacturer::OneByte(b),
+        [b1, b2, b3] => Manufacturer::ThreeByte(b1, b2, b3),
+        [] => return Err(ParseMessageError::MissingArgument),
+        _ => return Err(invalid(s[..open].trim()))
+    };
+    let data = s[open + 1..close].split_whitespace()
+        .map(parse_hex)
+        .collect::<Result<Vec<u8>, _>>()?;
+    if let Some(arg) = s[close + 1..].split_whitespace().next() {
+        return Err(ParseMessageError::UnexpectedArgument(arg.to_string()));
+    }
+    Ok(SysEx(manufacturer, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manufacturer::Manufacturer::*;
+    use types::Channel::*;
+
+    fn round_trip(message: Message, text: &str) {
+        assert_eq!(message.to_string(), text);
+        assert_eq!(text.parse(), Ok(message));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(Start, "start");
+        round_trip(TimingClock, "timing_clock");
+        round_trip(Continue, "continue");
+        round_trip(Stop, "stop");
+        round_trip(ActiveSensing, "active_sensing");
+        round_trip(SystemReset, "system_reset");
+        round_trip(MtcQuarterFrame(7, 15), "mtc_quarter_frame 7 15");
+        round_trip(SongPositionPointer(16383), "song_position 16383");
+        round_trip(AllSoundOff(Ch1), "all_sound_off ch1");
+        round_trip(ResetAllControllers(Ch2), "reset_all_controllers ch2");
+        round_trip(LocalControlOff(Ch3), "local_control_off ch3");
+        round_trip(LocalControlOn(Ch4), "local_control_on ch4");
+        round_trip(AllNotesOff(Ch16), "all_notes_off ch16");
+        round_trip(NoteOff(Ch1, 60, 64), "note_off ch1 C4 vel=64");
+        round_trip(ProgramChange(Ch10, 127), "pc ch10 127");
+        round_trip(ControlChange(Ch2, 7, 90), "cc ch2 volume 90");
+        round_trip(ControlChange(Ch2, 3, 90), "cc ch2 3 90");
+        round_trip(RPN7(Ch1, 0, 2), "rpn7 ch1 0 2");
+        round_trip(RPN14(Ch1, 0, 256), "rpn14 ch1 0 256");
+        round_trip(NRPN7(Ch1, 1000, 1), "nrpn7 ch1 1000 1");
+        round_trip(NRPN14(Ch1, 1000, 1001), "nrpn14 ch1 1000 1001");
+        round_trip(SysEx(OneByte(0x41), vec![0x10, 0x20, 0x30]), "sysex 0x41 [10 20 30]");
+        round_trip(SysEx(ThreeByte(0x00, 0x20, 0x33), vec![]), "sysex 0x00 0x20 0x33 []");
+        round_trip(NoteOn(Ch1, 60, 100), "note_on ch1 C4 vel=100");
+        round_trip(NoteOn(Ch1, 61, 100), "note_on ch1 C#4 vel=100");
+        round_trip(PitchBend(Ch1, 8192), "pitch_bend ch1 8192");
+        round_trip(PolyphonicPressure(Ch1, 0, 20), "poly_pressure ch1 C-1 20");
+        round_trip(ChannelPressure(Ch1, 20), "channel_pressure ch1 20");
+    }
+
+    #[test]
+    fn test_out_of_range_round_trip() {
+        round_trip(NoteOn(Ch1, 200, 255), "note_on ch1 200 vel=255");
+        round_trip(SysEx(OneByte(0xFF), vec![0x80]), "sysex 0xFF [80]");
+        round_trip(PitchBend(Ch1, 65535), "pitch_bend ch1 65535");
+    }
+
+    #[test]
+    fn test_from_str_variations() {
+        assert_eq!("  note_on   ch1 60 100 ".parse(), Ok(NoteOn(Ch1, 60, 100)));
+        assert_eq!("note_on ch1 Db4 vel=1".parse(), Ok(NoteOn(Ch1, 61, 1)));
+        assert_eq!("cc ch1 7 90".parse(), Ok(ControlChange(Ch1, 7, 90)));
+        assert_eq!("sysex 0x41 [1 a ff]".parse(), Ok(SysEx(OneByte(0x41), vec![1, 10, 255])));
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        use super::ParseMessageError::*;
+        assert_eq!("".parse::<Message>(), Err(Empty));
+        assert_eq!("foo ch1".parse::<Message>(), Err(UnknownMessage("foo".to_string())));
+        assert_eq!("note_on ch1 C4".parse::<Message>(), Err(MissingArgument));
+        assert_eq!("note_on ch0 C4 1".parse::<Message>(), Err(InvalidArgument("ch0".to_string())));
+        assert_eq!("note_on ch17 C4 1".parse::<Message>(),
+                   Err(InvalidArgument("ch17".to_string())));
+        assert_eq!("cc ch1 volum 1".parse::<Message>(), Err(InvalidArgument("volum".to_string())));
+        assert_eq!("stop now".parse::<Message>(), Err(UnexpectedArgument("now".to_string())));
+        assert_eq!("sysex [10]".parse::<Message>(), Err(MissingArgument));
+        assert_eq!("sysex 0x41 10".parse::<Message>(), Err(MissingArgument));
+        assert_eq!("sysex 0x41 [1G]".parse::<Message>(), Err(InvalidArgument("1G".to_string())));
+        assert_eq!("sysex 0x41 0x42 [10]".parse::<Message>(),
+                   Err(InvalidArgument("0x41 0x42".to_string())));
+        assert_eq!("sysex 0x41 [10] x".parse::<Message>(),
+                   Err(UnexpectedArgument("x".to_string())));
+    }
+}
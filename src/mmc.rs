@@ -0,0 +1,289 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::U7;
+use message::Message;
+use timecode::{Timecode, time_code_bytes, time_code_from_bytes};
+use universal::{UniversalSysEx, RealTime};
+use utils::mask7;
+
+/// The response field holding the current position of a device
+pub const MMC_SELECTED_TIME_CODE: U7 = 0x01;
+
+const LOCATE: U7 = 0x44;
+const LOCATE_TARGET: U7 = 0x01;
+
+/// A Midi Machine Control command, sent as a Universal Real Time SysEx
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    /// Play once the device has finished locating
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    /// Punch in, or start recording if stopped
+    RecordStrobe,
+    /// Punch out
+    RecordExit,
+    RecordPause,
+    Pause,
+    Eject,
+    Chase,
+    Reset,
+    /// Locate to a time, the second argument is the subframes (0-99)
+    Locate(Timecode, U7),
+    /// Any other command, the data does not include the byte count
+    ///
+    /// Only commands 0x40-0x77 have a byte count, so the data of any other command is not sent.
+    /// At most 127 bytes are sent.
+    Other(U7, Vec<U7>)
+}
+
+const COMMAND_IDS: [(MmcCommand, U7); 12] = [
+    (MmcCommand::Stop, 0x01),
+    (MmcCommand::Play, 0x02),
+    (MmcCommand::DeferredPlay, 0x03),
+    (MmcCommand::FastForward, 0x04),
+    (MmcCommand::Rewind, 0x05),
+    (MmcCommand::RecordStrobe, 0x06),
+    (MmcCommand::RecordExit, 0x07),
+    (MmcCommand::RecordPause, 0x08),
+    (MmcCommand::Pause, 0x09),
+    (MmcCommand::Eject, 0x0A),
+    (MmcCommand::Chase, 0x0B),
+    (MmcCommand::Reset, 0x0D)
+];
+
+/// A Midi Machine Control message holding several commands, they are carried out in order
+pub fn mmc_message(device: U7, commands: &[MmcCommand]) -> Message {
+    let mut data = vec![];
+    for command in commands {
+        let (id, fields) = match *command {
+            MmcCommand::Locate(time, subframes) => {
+                let mut fields = vec![LOCATE_TARGET];
+                fields.extend(time_code_bytes(&time, subframes));
+                (LOCATE, fields)
+            },
+            MmcCommand::Other(id, ref fields) => (mask7(id), fields.clone()),
+            _ => (COMMAND_IDS.iter().find(|c| c.0 == *command).map_or(0, |c| c.1), vec![])
+        };
+        push_field(&mut data, id, &fields, false);
+    }
+    UniversalSysEx::RealTime(device, RealTime::MachineControlCommand, data).to_message()
+}
+
+impl MmcCommand {
+    pub fn to_message(&self, device: U7) -> Message {
+        mmc_message(device, ::std::slice::from_ref(self))
+    }
+
+    /// Read a Midi Machine Control message, as the device ID and its commands, returns `None` if
+    /// the message is not one or is truncated
+    pub fn from_message(message: &Message) -> Option<(U7, Vec<MmcCommand>)> {
+        let (device, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::RealTime(device, RealTime::MachineControlCommand, data) => {
+                (device, data)
+            },
+            _ => return None
+        };
+        let commands = split_fields(&data, false)?.into_iter().map(|(id, fields)| {
+            if let Some(c) = COMMAND_IDS.iter().find(|c| c.1 == id) {
+                return c.0.clone();
+            }
+            match (id, fields) {
+                (LOCATE, &[LOCATE_TARGET, ref time @ ..]) => {
+                    time_code_from_bytes(time).map(|(t, st)| MmcCommand::Locate(t, st))
+                },
+                _ => None
+            }.unwrap_or_else(|| MmcCommand::Other(id, fields.to_vec()))
+        });
+        Some((device, commands.collect()))
+    }
+}
+
+/// A field of a Midi Machine Control response, sent as a Universal Real Time SysEx
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum MmcResponse {
+    /// A time code field, e.g. `MMC_SELECTED_TIME_CODE`, with the time and subframes
+    TimeCode(U7, Timecode, U7),
+    /// Any other field, the data does not include the byte count
+    ///
+    /// Fields 0x01-0x1F are sent with 5 bytes of data and 0x21-0x3F with 2, the data is padded with
+    /// 0s or truncated to fit. Fields 0x40-0x77 are sent with at most 127 bytes, and any other
+    /// fields without their data.
+    Other(U7, Vec<U7>)
+}
+
+/// A Midi Machine Control response message holding several fields
+pub fn mmc_response_message(device: U7, responses: &[MmcResponse]) -> Message {
+    let mut data = vec![];
+    for response in responses {
+        match *response {
+            MmcResponse::TimeCode(id, time, subframes) => {
+                data.push(mask7(id));
+                data.extend(time_code_bytes(&time, subframes));
+            },
+            MmcResponse::Other(id, ref fields) => push_field(&mut data, mask7(id), fields, true)
+        }
+    }
+    UniversalSysEx::RealTime(device, RealTime::MachineControlResponse, data).to_message()
+}
+
+impl MmcResponse {
+    /// Read a Midi Machine Control response message, as the device ID and its fields, returns
+    /// `None` if the message is not one or is truncated
+    pub fn from_message(message: &Message) -> Option<(U7, Vec<MmcResponse>)> {
+        let (device, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::RealTime(device, RealTime::MachineControlResponse, data) => {
+                (device, data)
+            },
+            _ => return None
+        };
+        let responses = split_fields(&data, true)?.into_iter().map(|(id, fields)| {
+            match id {
+                0x01..=0x1F => time_code_from_bytes(fields)
+                    .map(|(time, subframes)| MmcResponse::TimeCode(id, time, subframes)),
+                _ => None
+            }.unwrap_or_else(|| MmcResponse::Other(id, fields.to_vec()))
+        });
+        Some((device, responses.collect()))
+    }
+}
+
+// commands and responses 0x40-0x77 are followed by a byte count, responses 0x01-0x1F are
+// followed by a 5 byte time code and 0x21-0x3F by a 2 byte short time code, returns `None` if
+// the field has a byte count
+fn fixed_len(id: U7, response: bool) -> Option<usize> {
+    match id {
+        0x40..=0x77 => None,
+        0x01..=0x1F if response => Some(5),
+        0x21..=0x3F if response => Some(2),
+        _ => Some(0)
+    }
+}
+
+fn split_fields(mut data: &[U7], response: bool) -> Option<Vec<(U7, &[U7])>> {
+    let mut fields = vec![];
+    while let Some((&id, rest)) = data.split_first() {
+        let (start, len) = match fixed_len(id, response) {
+            Some(len) => (0, len),
+            None => (1, *rest.first()? as usize)
+        };
+        let end = start + len;
+        if rest.len() < end {
+            return None;
+        }
+        fields.push((id, &rest[start..end]));
+        data = &rest[end..];
+    }
+    Some(fields)
+}
+
+// the data is cut or padded to a length that `split_fields` reads back
+fn push_field(data: &mut Vec<U7>, id: U7, fields: &[U7], response: bool) {
+    data.push(id);
+    let len = match fixed_len(id, response) {
+        Some(len) => len,
+        None => {
+            let len = fields.len().min(127);
+            data.push(len as U7);
+            len
+        }
+    };
+    data.extend(fields.iter().map(|b| mask7(*b)).chain(::std::iter::repeat(0)).take(len));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::ALL_CALL;
+    use manufacturer::Manufacturer::*;
+    use timecode::FrameRate::*;
+    use Message::SysEx;
+
+    fn time() -> Timecode {
+        Timecode::new(1, 2, 3, 4, Fps25).unwrap()
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(MmcCommand::Play.to_message(ALL_CALL),
+                   SysEx(OneByte(0x7F), vec![0x7F, 0x06, 0x02]));
+        assert_eq!(MmcCommand::Reset.to_message(0x10),
+                   SysEx(OneByte(0x7F), vec![0x10, 0x06, 0x0D]));
+        let locate = SysEx(OneByte(0x7F),
+                           vec![0x7F, 0x06, 0x44, 0x06, 0x01, 0x21, 0x02, 0x03, 0x04, 0x00]);
+        assert_eq!(MmcCommand::Locate(time(), 0).to_message(ALL_CALL), locate);
+        assert_eq!(MmcCommand::from_message(&locate),
+                   Some((ALL_CALL, vec![MmcCommand::Locate(time(), 0)])));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let commands = vec![MmcCommand::Stop, MmcCommand::Play, MmcCommand::DeferredPlay,
+                            MmcCommand::FastForward, MmcCommand::Rewind,
+                            MmcCommand::RecordStrobe, MmcCommand::RecordExit,
+                            MmcCommand::RecordPause, MmcCommand::Pause, MmcCommand::Eject,
+                            MmcCommand::Chase, MmcCommand::Reset,
+                            MmcCommand::Locate(time(), 50),
+                            MmcCommand::Other(0x47, vec![0x01, 0x01]),
+                            MmcCommand::Other(0x0F, vec![])];
+        let message = mmc_message(0x10, &commands);
+        assert_eq!(MmcCommand::from_message(&message), Some((0x10, commands)));
+
+        // locate to a register rather than a time
+        let register = SysEx(OneByte(0x7F), vec![0x10, 0x06, 0x44, 0x02, 0x00, 0x08]);
+        assert_eq!(MmcCommand::from_message(&register),
+                   Some((0x10, vec![MmcCommand::Other(0x44, vec![0x00, 0x08])])));
+    }
+
+    #[test]
+    fn test_other_data() {
+        let long = vec![1; 130];
+        let message = mmc_message(0x10, &[MmcCommand::Other(0x47, long.clone()),
+                                          MmcCommand::Other(0x0F, vec![1, 2]),
+                                          MmcCommand::Play]);
+        assert_eq!(MmcCommand::from_message(&message),
+                   Some((0x10, vec![MmcCommand::Other(0x47, long[..127].to_vec()),
+                                    MmcCommand::Other(0x0F, vec![]),
+                                    MmcCommand::Play])));
+
+        let message = mmc_response_message(0x10, &[MmcResponse::Other(0x21, vec![5]),
+                                                   MmcResponse::Other(0x22, vec![1, 2, 3]),
+                                                   MmcResponse::Other(0x20, vec![4])]);
+        assert_eq!(message, SysEx(OneByte(0x7F),
+                                  vec![0x10, 0x07, 0x21, 0x05, 0x00, 0x22, 0x01, 0x02, 0x20]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let truncated = SysEx(OneByte(0x7F), vec![0x10, 0x06, 0x44, 0x06, 0x01, 0x21]);
+        assert_eq!(MmcCommand::from_message(&truncated), None);
+        let response = SysEx(OneByte(0x7F), vec![0x10, 0x07, 0x01, 0x21, 2, 3, 4, 0]);
+        assert_eq!(MmcCommand::from_message(&response), None);
+        assert_eq!(MmcResponse::from_message(&MmcCommand::Stop.to_message(0x10)), None);
+    }
+
+    #[test]
+    fn test_responses() {
+        // selected time code, with the color frame and final byte flags set
+        let message = SysEx(OneByte(0x7F),
+                            vec![0x10, 0x07, 0x01, 0x21, 0x42, 0x03, 0x24, 0x00,
+                                 0x21, 0x05, 0x00,
+                                 0x48, 0x01, 0x7F]);
+        let responses = vec![MmcResponse::TimeCode(MMC_SELECTED_TIME_CODE, time(), 0),
+                             MmcResponse::Other(0x21, vec![0x05, 0x00]),
+                             MmcResponse::Other(0x48, vec![0x7F])];
+        assert_eq!(MmcResponse::from_message(&message), Some((0x10, responses.clone())));
+
+        let message = mmc_response_message(0x10, &responses);
+        assert_eq!(message, SysEx(OneByte(0x7F),
+                                  vec![0x10, 0x07, 0x01, 0x21, 0x02, 0x03, 0x04, 0x00,
+                                       0x21, 0x05, 0x00,
+                                       0x48, 0x01, 0x7F]));
+        assert_eq!(MmcResponse::from_message(&message), Some((0x10, responses)));
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::{U7, U14};
+use message::Message;
+use timecode::{Timecode, time_code_bytes, time_code_from_bytes};
+use universal::{UniversalSysEx, RealTime};
+use utils::{mask7, msb_lsb_to_u14, u14_to_msb_lsb};
+
+/// The kind of equipment a Midi Show Control command is for
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum MscCommandFormat {
+    Lighting,
+    MovingLights,
+    ColorChangers,
+    Strobes,
+    Lasers,
+    Chasers,
+    Sound,
+    Music,
+    Machinery,
+    Video,
+    Projection,
+    ProcessControl,
+    Pyro,
+    /// All equipment, whatever its format
+    AllTypes,
+    Other(U7)
+}
+
+const FORMAT_IDS: [(MscCommandFormat, U7); 14] = [
+    (MscCommandFormat::Lighting, 0x01),
+    (MscCommandFormat::MovingLights, 0x02),
+    (MscCommandFormat::ColorChangers, 0x03),
+    (MscCommandFormat::Strobes, 0x04),
+    (MscCommandFormat::Lasers, 0x05),
+    (MscCommandFormat::Chasers, 0x06),
+    (MscCommandFormat::Sound, 0x10),
+    (MscCommandFormat::Music, 0x11),
+    (MscCommandFormat::Machinery, 0x20),
+    (MscCommandFormat::Video, 0x30),
+    (MscCommandFormat::Projection, 0x40),
+    (MscCommandFormat::ProcessControl, 0x50),
+    (MscCommandFormat::Pyro, 0x60),
+    (MscCommandFormat::AllTypes, 0x7F)
+];
+
+impl MscCommandFormat {
+    pub fn from_u7(id: U7) -> MscCommandFormat {
+        FORMAT_IDS.iter().find(|p| p.1 == id).map_or(MscCommandFormat::Other(id), |p| p.0)
+    }
+
+    pub fn to_u7(self) -> U7 {
+        match self {
+            MscCommandFormat::Other(id) => mask7(id),
+            _ => FORMAT_IDS.iter().find(|p| p.0 == self).map_or(0, |p| p.1)
+        }
+    }
+}
+
+/// The cue a Midi Show Control command refers to
+///
+/// Each field is ASCII digits with an optional decimal point, e.g. `"12.5"`. A list is only sent
+/// with a number, and a path only with a list, a field of `None` means the console's current one.
+///
+/// Any other characters in a field are not sent, and an empty number is read back as `None`, so
+/// only a cue where `is_valid` is true survives a round trip.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct MscCue {
+    pub number: Option<String>,
+    pub list: Option<String>,
+    pub path: Option<String>
+}
+
+impl MscCue {
+    /// The cue `number` in the current list, returns `None` if `number` is not valid
+    pub fn new(number: &str) -> Option<MscCue> {
+        let cue = MscCue { number: Some(number.to_owned()), list: None, path: None };
+        if cue.is_valid() { Some(cue) } else { None }
+    }
+
+    /// The cue `number` in `list`, returns `None` if either is not valid
+    pub fn in_list(number: &str, list: &str) -> Option<MscCue> {
+        let cue = MscCue { list: Some(list.to_owned()), ..MscCue::new(number)? };
+        if cue.is_valid() { Some(cue) } else { None }
+    }
+
+    /// True if every field that is set is made of ASCII digits and decimal points, and is not empty
+    pub fn is_valid(&self) -> bool {
+        [&self.number, &self.list, &self.path].iter().all(|f| match **f {
+            Some(ref f) => !f.is_empty() && f.bytes().all(is_cue_byte),
+            None => true
+        })
+    }
+
+    fn to_u7s(&self) -> Vec<U7> {
+        let fields = [&self.number, &self.list, &self.path];
+        let fields = fields.iter().take_while(|f| f.is_some()).flat_map(|f| f.as_ref());
+        let mut data = vec![];
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                data.push(0);
+            }
+            data.extend(field.bytes().filter(|b| is_cue_byte(*b)));
+        }
+        data
+    }
+
+    fn from_u7s(data: &[U7]) -> MscCue {
+        if data.is_empty() {
+            return MscCue::default();
+        }
+        let mut fields = data.split(|b| *b == 0).map(|f| f.iter().map(|b| *b as char).collect());
+        MscCue { number: fields.next(), list: fields.next(), path: fields.next() }
+    }
+}
+
+fn is_cue_byte(b: u8) -> bool {
+    b.is_ascii_digit() || b == b'.'
+}
+
+/// A Midi Show Control command, sent as a Universal Real Time SysEx
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum MscCommand {
+    Go(MscCue),
+    Stop(MscCue),
+    Resume(MscCue),
+    /// Go at a time, with its subframes
+    TimedGo(Timecode, U7, MscCue),
+    Load(MscCue),
+    /// Set a generic control (e.g. a fader) to a value, at an optional time and subframes
+    Set(U14, U14, Option<(Timecode, U7)>),
+    /// Fire a macro
+    Fire(U7),
+    AllOff,
+    Restore,
+    Reset,
+    GoOff(MscCue),
+    /// Any other command, with its data
+    Other(U7, Vec<U7>)
+}
+
+const GO: U7 = 0x01;
+const STOP: U7 = 0x02;
+const RESUME: U7 = 0x03;
+const TIMED_GO: U7 = 0x04;
+const LOAD: U7 = 0x05;
+const SET: U7 = 0x06;
+const FIRE: U7 = 0x07;
+const ALL_OFF: U7 = 0x08;
+const RESTORE: U7 = 0x09;
+const RESET: U7 = 0x0A;
+const GO_OFF: U7 = 0x0B;
+
+impl MscCommand {
+    /// The message sending this command to `device` (or `ALL_CALL`), for equipment of `format`
+    pub fn to_message(&self, device: U7, format: MscCommandFormat) -> Message {
+        let (command, data) = match *self {
+            MscCommand::Go(ref cue) => (GO, cue.to_u7s()),
+            MscCommand::Stop(ref cue) => (STOP, cue.to_u7s()),
+            MscCommand::Resume(ref cue) => (RESUME, cue.to_u7s()),
+            MscCommand::TimedGo(ref time, subframes, ref cue) => {
+                let mut data = time_code_bytes(time, subframes).to_vec();
+                data.extend(cue.to_u7s());
+                (TIMED_GO, data)
+            },
+            MscCommand::Load(ref cue) => (LOAD, cue.to_u7s()),
+            MscCommand::Set(control, value, time) => {
+                let (control_msb, control_lsb) = u14_to_msb_lsb(control);
+                let (value_msb, value_lsb) = u14_to_msb_lsb(value);
+                let mut data = vec![control_lsb, control_msb, value_lsb, value_msb];
+                if let Some((ref time, subframes)) = time {
+                    data.extend_from_slice(&time_code_bytes(time, subframes));
+                }
+                (SET, data)
+            },
+            MscCommand::Fire(macro_number) => (FIRE, vec![mask7(macro_number)]),
+            MscCommand::AllOff => (ALL_OFF, vec![]),
+            MscCommand::Restore => (RESTORE, vec![]),
+            MscCommand::Reset => (RESET, vec![]),
+            MscCommand::GoOff(ref cue) => (GO_OFF, cue.to_u7s()),
+            MscCommand::Other(command, ref data) => {
+                (mask7(command), data.iter().map(|b| mask7(*b)).collect())
+            }
+        };
+        let mut bytes = vec![format.to_u7(), command];
+        bytes.extend(data);
+        UniversalSysEx::RealTime(device, RealTime::ShowControl, bytes).to_message()
+    }
+
+    /// Read a Midi Show Control message, as the device ID, the command format and the command,
+    /// returns `None` if the message is not one
+    ///
+    /// Commands with malformed data are read as `MscCommand::Other`.
+    pub fn from_message(message: &Message) -> Option<(U7, MscCommandFormat, MscCommand)> {
+        let (device, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::RealTime(device, RealTime::ShowControl, data) => (device, data),
+            _ => return None
+        };
+        let (format, command, data) = match data[..] {
+            [format, command, ref data @ ..] => (format, command, data),
+            _ => return None
+        };
+        let parsed = match (command, data) {
+            (GO, _) => Some(MscCommand::Go(MscCue::from_u7s(data))),
+            (STOP, _) => Some(MscCommand::Stop(MscCue::from_u7s(data))),
+            (RESUME, _) => Some(MscCommand::Resume(MscCue::from_u7s(data))),
+            (TIMED_GO, _) if data.len() >= 5 => {
+                time_code_from_bytes(&data[..5]).map(|(time, subframes)| {
+                    MscCommand::TimedGo(time, subframes, MscCue::from_u7s(&data[5..]))
+                })
+            },
+            (LOAD, _) => Some(MscCommand::Load(MscCue::from_u7s(data))),
+            (SET, &[c_lsb, c_msb, v_lsb, v_msb, ref time @ ..]) => {
+                let time = match time {
+                    [] => Some(None),
+                    _ => time_code_from_bytes(time).map(Some)
+                };
+                time.map(|time| MscCommand::Set(msb_lsb_to_u14(c_msb, c_lsb),
+                                                msb_lsb_to_u14(v_msb, v_lsb), time))
+            },
+            (FIRE, &[macro_number]) => Some(MscCommand::Fire(macro_number)),
+            (ALL_OFF, &[]) => Some(MscCommand::AllOff),
+            (RESTORE, &[]) => Some(MscCommand::Restore),
+            (RESET, &[]) => Some(MscCommand::Reset),
+            (GO_OFF, _) => Some(MscCommand::GoOff(MscCue::from_u7s(data))),
+            _ => None
+        };
+        let command = parsed.unwrap_or_else(|| MscCommand::Other(command, data.to_vec()));
+        Some((device, MscCommandFormat::from_u7(format), command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::ALL_CALL;
+    use manufacturer::Manufacturer::*;
+    use timecode::FrameRate::*;
+    use Message::SysEx;
+
+    fn round_trip(command: MscCommand) {
+        let message = command.to_message(0x01, MscCommandFormat::Lighting);
+        assert_eq!(MscCommand::from_message(&message),
+                   Some((0x01, MscCommandFormat::Lighting, command)));
+    }
+
+    #[test]
+    fn test_go() {
+        // GO cue 235.6 in list 36.6
+        let message = SysEx(OneByte(0x7F),
+                            vec![0x7F, 0x02, 0x01, 0x01,
+                                 0x32, 0x33, 0x35, 0x2E, 0x36, 0x00, 0x33, 0x36, 0x2E, 0x36]);
+        let go = MscCommand::Go(MscCue::in_list("235.6", "36.6").unwrap());
+        assert_eq!(go.to_message(ALL_CALL, MscCommandFormat::Lighting), message);
+        assert_eq!(MscCommand::from_message(&message),
+                   Some((ALL_CALL, MscCommandFormat::Lighting, go)));
+
+        // the current cue
+        let message = MscCommand::Go(MscCue::default()).to_message(0x10, MscCommandFormat::Sound);
+        assert_eq!(message, SysEx(OneByte(0x7F), vec![0x10, 0x02, 0x10, 0x01]));
+    }
+
+    #[test]
+    fn test_commands() {
+        let time = Timecode::new(1, 2, 3, 4, Fps30).unwrap();
+        let path = MscCue {
+            number: Some("1".to_owned()),
+            list: Some("2".to_owned()),
+            path: Some("3".to_owned())
+        };
+        round_trip(MscCommand::Stop(MscCue::new("5").unwrap()));
+        round_trip(MscCommand::Resume(path.clone()));
+        round_trip(MscCommand::TimedGo(time, 0, path));
+        round_trip(MscCommand::TimedGo(time, 10, MscCue::default()));
+        round_trip(MscCommand::Load(MscCue::new("1.5").unwrap()));
+        round_trip(MscCommand::Set(300, 16383, None));
+        round_trip(MscCommand::Set(1, 2, Some((time, 0))));
+        round_trip(MscCommand::Fire(12));
+        round_trip(MscCommand::AllOff);
+        round_trip(MscCommand::Restore);
+        round_trip(MscCommand::Reset);
+        round_trip(MscCommand::GoOff(MscCue::new("7").unwrap()));
+        round_trip(MscCommand::Other(0x11, vec![0x31]));
+
+        assert_eq!(MscCommand::Set(300, 16383, None).to_message(0x01, MscCommandFormat::Sound),
+                   SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x10, 0x06, 0x2C, 0x02, 0x7F, 0x7F]));
+        assert_eq!(MscCommand::Fire(12).to_message(0x01, MscCommandFormat::Pyro),
+                   SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x60, 0x07, 0x0C]));
+    }
+
+    #[test]
+    fn test_cue() {
+        assert!(MscCue::new("12.5").is_some());
+        assert_eq!(MscCue::new(""), None);
+        assert_eq!(MscCue::new("1a"), None);
+        assert_eq!(MscCue::in_list("1", "\u{e9}"), None);
+        assert!(MscCue::default().is_valid());
+
+        // invalid characters are not sent
+        let cue = MscCue { number: Some("1\u{e9}2".to_owned()), list: None, path: None };
+        assert!(!cue.is_valid());
+        let message = MscCommand::Go(cue).to_message(0x01, MscCommandFormat::Lighting);
+        assert_eq!(message, SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x01, 0x01, 0x31, 0x32]));
+
+        // an empty number is the current cue
+        let cue = MscCue { number: Some(String::new()), list: None, path: None };
+        let message = MscCommand::Go(cue).to_message(0x01, MscCommandFormat::Lighting);
+        assert_eq!(MscCommand::from_message(&message),
+                   Some((0x01, MscCommandFormat::Lighting, MscCommand::Go(MscCue::default()))));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let short = SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x01]);
+        assert_eq!(MscCommand::from_message(&short), None);
+        let fire = SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x01, 0x07]);
+        assert_eq!(MscCommand::from_message(&fire),
+                   Some((0x01, MscCommandFormat::Lighting, MscCommand::Other(0x07, vec![]))));
+        let timed_go = SysEx(OneByte(0x7F), vec![0x01, 0x02, 0x01, 0x04, 0x21]);
+        assert_eq!(MscCommand::from_message(&timed_go),
+                   Some((0x01, MscCommandFormat::Lighting, MscCommand::Other(0x04, vec![0x21]))));
+        let mmc_stop = SysEx(OneByte(0x7F), vec![0x7F, 0x06, 0x01]);
+        assert_eq!(MscCommand::from_message(&mmc_stop), None);
+    }
+
+    #[test]
+    fn test_formats() {
+        for id in 0..128 {
+            assert_eq!(MscCommandFormat::from_u7(id).to_u7(), id);
+        }
+    }
+}
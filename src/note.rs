@@ -0,0 +1,119 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use types::U7;
+
+const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// A Midi note number
+///
+/// Notes are displayed and parsed using scientific pitch notation, where middle C (60) is `C4`,
+/// and note 0 is `C-1`. Sharps are displayed, sharps and flats can be parsed, e.g. `C#4` or `Db4`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Note(pub U7);
+
+impl Note {
+    /// The pitch class of this note, `C = 0`, `C# = 1`, etc
+    pub fn pitch_class(self) -> u8 {
+        self.0 % 12
+    }
+
+    /// The octave of this note, where middle C is in octave 4
+    pub fn octave(self) -> i8 {
+        (self.0 / 12) as i8 - 1
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", NAMES[self.pitch_class() as usize], self.octave())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ParseNoteError;
+
+impl fmt::Display for ParseNoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid note name")
+    }
+}
+
+impl Error for ParseNoteError {}
+
+impl FromStr for Note {
+    type Err = ParseNoteError;
+
+    fn from_str(s: &str) -> Result<Note, ParseNoteError> {
+        let mut chars = s.chars();
+        let pitch_class: i32 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(ParseNoteError)
+        };
+        let rest = chars.as_str();
+        let (accidental, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (1, octave)
+        }
+        else if let Some(octave) = rest.strip_prefix('b') {
+            (-1, octave)
+        }
+        else {
+            (0, rest)
+        };
+        let octave: i32 = octave.parse().map_err(|_| ParseNoteError)?;
+        let note = (octave + 1) * 12 + pitch_class + accidental;
+        if (0..128).contains(&note) {
+            Ok(Note(note as U7))
+        }
+        else {
+            Err(ParseNoteError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Note(60).to_string(), "C4");
+        assert_eq!(Note(61).to_string(), "C#4");
+        assert_eq!(Note(69).to_string(), "A4");
+        assert_eq!(Note(0).to_string(), "C-1");
+        assert_eq!(Note(127).to_string(), "G9");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("C4".parse(), Ok(Note(60)));
+        assert_eq!("c#4".parse(), Ok(Note(61)));
+        assert_eq!("Db4".parse(), Ok(Note(61)));
+        assert_eq!("Cb4".parse(), Ok(Note(59)));
+        assert_eq!("C-1".parse(), Ok(Note(0)));
+        assert_eq!("G9".parse(), Ok(Note(127)));
+        assert_eq!("G#9".parse::<Note>(), Err(ParseNoteError));
+        assert_eq!("Cb-1".parse::<Note>(), Err(ParseNoteError));
+        assert_eq!("H4".parse::<Note>(), Err(ParseNoteError));
+        assert_eq!("C".parse::<Note>(), Err(ParseNoteError));
+    }
+
+    #[test]
+    fn test_all_round_trip() {
+        for i in 0..128 {
+            assert_eq!(Note(i).to_string().parse(), Ok(Note(i)));
+        }
+    }
+}
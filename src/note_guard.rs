@@ -0,0 +1,186 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use num::FromPrimitive;
+
+use constants::*;
+use types::{Channel, U7};
+use message::Message;
+use Message::*;
+use utils::mask7;
+
+/// Velocity used for the `NoteOff`s generated to silence notes
+const RELEASE_VELOCITY: U7 = 64;
+
+/// Watches outgoing messages and tracks every sounding note, so that hanging notes can be stopped
+///
+/// A note that has been started more than once is counted, and is sent a `NoteOff` for each
+/// `NoteOn`, in case the receiver stacks voices. Sustain and sostenuto pedals that are down are
+/// tracked too, as they would keep notes sounding after their `NoteOff`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct NoteGuard {
+    notes: BTreeMap<(Channel, U7), usize>,
+    pedals: BTreeSet<(Channel, U7)>
+}
+
+impl NoteGuard {
+    pub fn new() -> NoteGuard {
+        NoteGuard::default()
+    }
+
+    /// Track a message as it is sent, returns any messages that should be sent after it
+    ///
+    /// After a `Stop` the messages from `silence` are returned, otherwise nothing is.
+    pub fn process(&mut self, message: &Message) -> Vec<Message> {
+        match *message {
+            NoteOn(ch, note, 0) | NoteOff(ch, note, _) => {
+                let key = (ch, mask7(note));
+                if let Some(count) = self.notes.get_mut(&key) {
+                    *count -= 1;
+                }
+                if self.notes.get(&key) == Some(&0) {
+                    self.notes.remove(&key);
+                }
+            },
+            NoteOn(ch, note, _) => *self.notes.entry((ch, mask7(note))).or_insert(0) += 1,
+            ControlChange(ch, cc, val) => match mask7(cc) {
+                CC_SUSTAIN | CC_SOSTENUTO if val >= 64 => {
+                    self.pedals.insert((ch, mask7(cc)));
+                },
+                CC_SUSTAIN | CC_SOSTENUTO => {
+                    self.pedals.remove(&(ch, mask7(cc)));
+                },
+                CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF..=CC_POLY_ON => self.notes_off(ch),
+                CC_RESET_ALL_CONTROLLERS => self.pedals_off(ch),
+                _ => ()
+            },
+            AllSoundOff(ch) | AllNotesOff(ch) => self.notes_off(ch),
+            ResetAllControllers(ch) => self.pedals_off(ch),
+            SystemReset => *self = NoteGuard::new(),
+            Stop => return self.silence(),
+            _ => ()
+        }
+        Vec::new()
+    }
+
+    /// Returns `true` if any notes are sounding
+    pub fn is_sounding(&self) -> bool {
+        !self.notes.is_empty()
+    }
+
+    /// The sounding notes on `channel`, lowest first
+    pub fn notes(&self, channel: Channel) -> Vec<U7> {
+        self.notes.keys().filter(|k| k.0 == channel).map(|k| k.1).collect()
+    }
+
+    /// The messages needed to stop every sounding note, a `NoteOff` for each one and a release
+    /// for any pedals that are down
+    ///
+    /// The notes are forgotten, as they are assumed to have been sent. Use this on demand, or when
+    /// the output is about to be disconnected.
+    pub fn silence(&mut self) -> Vec<Message> {
+        let mut messages: Vec<Message> = Vec::new();
+        for (&(ch, note), &count) in &self.notes {
+            for _ in 0..count {
+                messages.push(NoteOff(ch, note, RELEASE_VELOCITY));
+            }
+        }
+        messages.extend(self.pedals.iter().map(|&(ch, cc)| ControlChange(ch, cc, 0)));
+        messages.sort();
+        *self = NoteGuard::new();
+        messages
+    }
+
+    /// As `silence`, followed by `AllSoundOff`, `AllNotesOff` and `ResetAllControllers` on all 16
+    /// channels
+    pub fn panic(&mut self) -> Vec<Message> {
+        let mut messages = self.silence();
+        for ch in 0..16 {
+            let ch = FromPrimitive::from_u8(ch).unwrap();
+            messages.push(AllSoundOff(ch));
+            messages.push(AllNotesOff(ch));
+            messages.push(ResetAllControllers(ch));
+        }
+        messages
+    }
+
+    fn notes_off(&mut self, channel: Channel) {
+        self.notes.retain(|k, _| k.0 != channel);
+    }
+
+    fn pedals_off(&mut self, channel: Channel) {
+        self.pedals.retain(|k| k.0 != channel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Channel::*;
+
+    fn guard(messages: &[Message]) -> NoteGuard {
+        let mut guard = NoteGuard::new();
+        for message in messages {
+            assert_eq!(guard.process(message), vec![]);
+        }
+        guard
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut g = guard(&[NoteOn(Ch1, 60, 100),
+                            NoteOn(Ch1, 60, 100),
+                            NoteOn(Ch1, 62, 100),
+                            NoteOn(Ch1, 62, 0),
+                            NoteOn(Ch2, 64, 100),
+                            NoteOff(Ch3, 65, 0),
+                            ControlChange(Ch2, CC_SUSTAIN, 127),
+                            ControlChange(Ch3, CC_SOSTENUTO, 127),
+                            ControlChange(Ch3, CC_SOSTENUTO, 0)]);
+        assert_eq!(g.notes(Ch1), vec![60]);
+        assert_eq!(g.silence(), vec![NoteOff(Ch1, 60, 64),
+                                     NoteOff(Ch1, 60, 64),
+                                     NoteOff(Ch2, 64, 64),
+                                     ControlChange(Ch2, CC_SUSTAIN, 0)]);
+        assert!(!g.is_sounding());
+        assert_eq!(g.silence(), vec![]);
+    }
+
+    #[test]
+    fn test_channel_mode() {
+        let mut g = guard(&[NoteOn(Ch1, 60, 100),
+                            NoteOn(Ch2, 60, 100),
+                            NoteOn(Ch3, 60, 100),
+                            ControlChange(Ch3, CC_SUSTAIN, 127),
+                            AllNotesOff(Ch1),
+                            ControlChange(Ch2, CC_ALL_SOUND_OFF, 0),
+                            ResetAllControllers(Ch3)]);
+        assert_eq!(g.silence(), vec![NoteOff(Ch3, 60, 64)]);
+
+        let mut g = guard(&[NoteOn(Ch1, 60, 100), SystemReset]);
+        assert_eq!(g.silence(), vec![]);
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut g = guard(&[NoteOn(Ch1, 60, 100)]);
+        assert_eq!(g.process(&Stop), vec![NoteOff(Ch1, 60, 64)]);
+        assert_eq!(g.process(&Stop), vec![]);
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut g = guard(&[NoteOn(Ch1, 60, 100)]);
+        let panic = g.panic();
+        assert_eq!(panic.len(), 1 + 16 * 3);
+        assert_eq!(&panic[..4], &[NoteOff(Ch1, 60, 64),
+                                  AllSoundOff(Ch1),
+                                  AllNotesOff(Ch1),
+                                  ResetAllControllers(Ch1)]);
+        assert_eq!(panic[48], ResetAllControllers(Ch16));
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::{Channel, U7};
+use message::Message;
+use Message::{NoteOn, NoteOff};
+use track::{Event, Track};
+
+/// Velocity used for the `NoteOff` when a `NoteOn` with velocity 0 ends a note, as per the spec
+const IMPLIED_OFF_VELOCITY: U7 = 64;
+
+/// A note with a start time and duration, as found by pairing `NoteOn` and `NoteOff` messages
+///
+/// Spans are ordered by `start` first.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NoteSpan {
+    pub start: u32,
+    pub duration: u32,
+    pub channel: Channel,
+    pub note: U7,
+    pub on_velocity: U7,
+    pub off_velocity: U7
+}
+
+impl NoteSpan {
+    /// Convert back to a `NoteOn` and `NoteOff` event
+    ///
+    /// A span with a duration of 0 is ended 1 tick after it starts, as a `Track` sorts a `NoteOff`
+    /// before a `NoteOn` at the same tick, which would leave the note held.
+    pub fn to_events(&self) -> (Event, Event) {
+        let on = Event::new(self.start, NoteOn(self.channel, self.note, self.on_velocity));
+        let off = Event::new(self.start.saturating_add(self.duration.max(1)),
+                             NoteOff(self.channel, self.note, self.off_velocity));
+        (on, off)
+    }
+}
+
+/// Decides which note is ended when a `NoteOff` arrives for a pitch that is sounding more than once
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Overlap {
+    /// First in, first out. The earliest note is ended.
+    Fifo,
+    /// Last in, first out. The most recent note is ended.
+    Lifo
+}
+
+/// Pairs up `NoteOn` and `NoteOff` messages as they arrive
+///
+/// A `NoteOn` with a velocity of 0 is treated as a `NoteOff` with a velocity of 64. A `NoteOff`
+/// without a matching `NoteOn` is ignored, and one with an earlier tick than its `NoteOn` ends a
+/// note with a duration of 0.
+#[derive(Debug, Clone)]
+pub struct SpanCollector {
+    overlap: Overlap,
+    held: Vec<(Channel, U7, u32, U7)>
+}
+
+impl SpanCollector {
+    pub fn new(overlap: Overlap) -> SpanCollector {
+        SpanCollector { overlap, held: Vec::new() }
+    }
+
+    /// Process a message at `tick`, returns a `NoteSpan` if a note was ended
+    pub fn process(&mut self, tick: u32, message: &Message) -> Option<NoteSpan> {
+        match *message {
+            NoteOn(ch, note, 0) => self.end(tick, ch, note, IMPLIED_OFF_VELOCITY),
+            NoteOn(ch, note, vel) => {
+                self.held.push((ch, note, tick, vel));
+                None
+            },
+            NoteOff(ch, note, vel) => self.end(tick, ch, note, vel),
+            _ => None
+        }
+    }
+
+    /// End all the notes that are still held at `tick`, with an off velocity of 0
+    pub fn finish(&mut self, tick: u32) -> Vec<NoteSpan> {
+        self.held.drain(..).map(|(channel, note, start, on_velocity)| {
+            NoteSpan {
+                start,
+                duration: tick.saturating_sub(start),
+                channel,
+                note,
+                on_velocity,
+                off_velocity: 0
+            }
+        }).collect()
+    }
+
+    fn end(&mut self, tick: u32, channel: Channel, note: U7, off_velocity: U7) -> Option<NoteSpan> {
+        let matches = |h: &(Channel, U7, u32, U7)| h.0 == channel && h.1 == note;
+        let index = match self.overlap {
+            Overlap::Fifo => self.held.iter().position(matches),
+            Overlap::Lifo => self.held.iter().rposition(matches)
+        };
+        index.map(|i| {
+            let (_, _, start, on_velocity) = self.held.remove(i);
+            NoteSpan {
+                start,
+                duration: tick.saturating_sub(start),
+                channel,
+                note,
+                on_velocity,
+                off_velocity
+            }
+        })
+    }
+}
+
+/// Find all the notes in a list of time-sorted events
+///
+/// Notes still held after the last event are ended at the tick of the last event. The spans are
+/// returned sorted.
+pub fn note_spans(events: &[Event], overlap: Overlap) -> Vec<NoteSpan> {
+    let mut collector = SpanCollector::new(overlap);
+    let mut spans: Vec<NoteSpan> = events.iter()
+        .filter_map(|e| collector.process(e.tick, &e.message))
+        .collect();
+    let end = events.last().map_or(0, |e| e.tick);
+    spans.extend(collector.finish(end));
+    spans.sort();
+    spans
+}
+
+/// Convert `NoteSpan`s back to a `Track` of `NoteOn` and `NoteOff` messages
+///
+/// A span with a duration of 0 is given a duration of 1, see `NoteSpan::to_events`.
+pub fn spans_to_track(spans: &[NoteSpan]) -> Track {
+    let mut events = Vec::with_capacity(spans.len() * 2);
+    for span in spans {
+        let (on, off) = span.to_events();
+        events.push(on);
+        events.push(off);
+    }
+    Track::from_events(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Channel::*;
+
+    fn span(start: u32, duration: u32, note: U7, on: U7, off: U7) -> NoteSpan {
+        NoteSpan {
+            start,
+            duration,
+            channel: Ch1,
+            note,
+            on_velocity: on,
+            off_velocity: off
+        }
+    }
+
+    #[test]
+    fn test_note_spans() {
+        let track = Track::from_deltas(vec![(0, NoteOn(Ch1, 60, 100)),
+                                            (0, NoteOn(Ch2, 60, 90)),
+                                            (10, NoteOff(Ch1, 60, 20)),
+                                            (5, NoteOn(Ch2, 60, 0)),
+                                            (0, NoteOff(Ch3, 60, 0))]).unwrap();
+        let spans = note_spans(track.events(), Overlap::Fifo);
+        assert_eq!(spans, vec![span(0, 10, 60, 100, 20),
+                               NoteSpan { channel: Ch2, ..span(0, 15, 60, 90, 64) }]);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let track = Track::from_deltas(vec![(0, NoteOn(Ch1, 60, 100)),
+                                            (10, NoteOn(Ch1, 60, 90)),
+                                            (10, NoteOff(Ch1, 60, 1)),
+                                            (10, NoteOff(Ch1, 60, 2))]).unwrap();
+        assert_eq!(note_spans(track.events(), Overlap::Fifo),
+                   vec![span(0, 20, 60, 100, 1), span(10, 20, 60, 90, 2)]);
+        assert_eq!(note_spans(track.events(), Overlap::Lifo),
+                   vec![span(0, 30, 60, 100, 2), span(10, 10, 60, 90, 1)]);
+    }
+
+    #[test]
+    fn test_hanging() {
+        let track = Track::from_deltas(vec![(0, NoteOn(Ch1, 60, 100)),
+                                            (10, NoteOn(Ch1, 62, 100)),
+                                            (10, NoteOff(Ch1, 62, 0))]).unwrap();
+        assert_eq!(note_spans(track.events(), Overlap::Fifo),
+                   vec![span(0, 20, 60, 100, 0), span(10, 10, 62, 100, 0)]);
+    }
+
+    #[test]
+    fn test_spans_to_track() {
+        let spans = vec![span(0, 20, 60, 100, 1), span(10, 20, 62, 90, 2)];
+        let track = spans_to_track(&spans);
+        assert_eq!(track.to_deltas(), vec![(0, NoteOn(Ch1, 60, 100)),
+                                           (10, NoteOn(Ch1, 62, 90)),
+                                           (10, NoteOff(Ch1, 60, 1)),
+                                           (10, NoteOff(Ch1, 62, 2))]);
+        assert_eq!(note_spans(track.events(), Overlap::Fifo), spans);
+    }
+
+    #[test]
+    fn test_zero_duration() {
+        let spans = vec![span(0, 0, 60, 100, 1), span(10, 5, 62, 90, 2)];
+        let track = spans_to_track(&spans);
+        assert_eq!(track.to_deltas(), vec![(0, NoteOn(Ch1, 60, 100)),
+                                           (1, NoteOff(Ch1, 60, 1)),
+                                           (9, NoteOn(Ch1, 62, 90)),
+                                           (5, NoteOff(Ch1, 62, 2))]);
+        // no notes are left held to the end of the track
+        assert_eq!(note_spans(track.events(), Overlap::Fifo),
+                   vec![span(0, 1, 60, 100, 1), span(10, 5, 62, 90, 2)]);
+    }
+
+    #[test]
+    fn test_end_of_time() {
+        let (on, off) = span(u32::MAX - 5, 10, 60, 100, 1).to_events();
+        assert_eq!((on.tick, off.tick), (u32::MAX - 5, u32::MAX));
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut collector = SpanCollector::new(Overlap::Fifo);
+        assert_eq!(collector.process(10, &NoteOn(Ch1, 60, 100)), None);
+        assert_eq!(collector.process(5, &NoteOff(Ch1, 60, 1)), Some(span(10, 0, 60, 100, 1)));
+    }
+}
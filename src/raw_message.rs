@@ -6,6 +6,7 @@
 use types::U7;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
 pub enum RawMessage {
     Status(u8),
     StatusData(u8, U7),
@@ -14,3 +15,14 @@ pub enum RawMessage {
     Raw(u8)
 }
 
+impl RawMessage {
+    /// The bytes of this message, as sent on the wire
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            RawMessage::Status(s) => vec!(s),
+            RawMessage::StatusData(s, d1) => vec!(s, d1),
+            RawMessage::StatusDataData(s, d1, d2) => vec!(s, d1, d2),
+            RawMessage::Raw(b) => vec!(b)
+        }
+    }
+}
@@ -0,0 +1,284 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+
+use smf::{Smf, SmfError};
+
+/// A chunk from a RIFF file, `id` is the four character code identifying the chunk
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RiffChunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>
+}
+
+/// The INFO list of an RMID file
+///
+/// The common text fields are decoded to strings, all other sub-chunks are kept in `other`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RmidInfo {
+    /// INAM
+    pub title: Option<String>,
+    /// ICOP
+    pub copyright: Option<String>,
+    /// ICMT
+    pub comments: Option<String>,
+    pub other: Vec<RiffChunk>
+}
+
+/// A RIFF-wrapped Standard Midi File (`.rmi`)
+///
+/// `smf` holds the bytes of the Standard Midi File from the `data` chunk, use the `smf()` method
+/// to read them as an `Smf`. An embedded DLS bank is kept in `dls` (starting with the `DLS ` form
+/// type), any chunks that are not understood or are repeated are kept in `other` and written back
+/// out after the known chunks.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Rmid {
+    pub smf: Vec<u8>,
+    pub info: RmidInfo,
+    pub dls: Option<Vec<u8>>,
+    pub other: Vec<RiffChunk>
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RmidError {
+    /// The data does not start with a `RIFF` header
+    NotRiff,
+    /// The RIFF form type is not `RMID`
+    NotRmid,
+    /// A chunk extends past the end of the data
+    Truncated,
+    /// There is no `data` chunk
+    MissingData
+}
+
+impl fmt::Display for RmidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            RmidError::NotRiff => "not a RIFF file",
+            RmidError::NotRmid => "RIFF form type is not RMID",
+            RmidError::Truncated => "RIFF chunk is truncated",
+            RmidError::MissingData => "RMID file has no data chunk"
+        };
+        f.write_str(s)
+    }
+}
+
+impl Error for RmidError {}
+
+impl Rmid {
+    pub fn new(smf: Vec<u8>) -> Rmid {
+        Rmid { smf, ..Rmid::default() }
+    }
+
+    /// Wrap a Standard Midi File, see `Smf::to_bytes` for the errors
+    pub fn from_smf(smf: &Smf) -> Result<Rmid, SmfError> {
+        Ok(Rmid::new(smf.to_bytes()?))
+    }
+
+    /// Read the Standard Midi File from the `data` chunk
+    pub fn smf(&self) -> Result<Smf, SmfError> {
+        Smf::read(&self.smf)
+    }
+
+    /// Read an RMID file
+    pub fn read(bytes: &[u8]) -> Result<Rmid, RmidError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" {
+            return Err(RmidError::NotRiff);
+        }
+        if &bytes[8..12] != b"RMID" {
+            return Err(RmidError::NotRmid);
+        }
+        // some writers get the RIFF size wrong, so trust the length of the data instead
+        let mut rmid = Rmid::default();
+        // only the first of each known chunk is read, any repeats are kept in `other`
+        let mut smf = None;
+        let mut info = None;
+        for chunk in read_chunks(&bytes[12..])? {
+            match &chunk.id {
+                b"data" if smf.is_none() => smf = Some(chunk.data),
+                b"LIST" if info.is_none() && chunk.data.starts_with(b"INFO") => {
+                    info = Some(read_info(&chunk.data[4..])?)
+                },
+                b"RIFF" if rmid.dls.is_none() && chunk.data.starts_with(b"DLS ") => {
+                    rmid.dls = Some(chunk.data)
+                },
+                _ => rmid.other.push(chunk)
+            }
+        }
+        rmid.smf = smf.ok_or(RmidError::MissingData)?;
+        rmid.info = info.unwrap_or_default();
+        Ok(rmid)
+    }
+
+    /// Write an RMID file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"RMID");
+        write_chunk(&mut body, b"data", &self.smf);
+        let info = write_info(&self.info);
+        if info.len() > 4 {
+            write_chunk(&mut body, b"LIST", &info);
+        }
+        if let Some(ref dls) = self.dls {
+            write_chunk(&mut body, b"RIFF", dls);
+        }
+        for chunk in &self.other {
+            write_chunk(&mut body, &chunk.id, &chunk.data);
+        }
+        let mut output = Vec::with_capacity(body.len() + 8);
+        write_chunk(&mut output, b"RIFF", &body);
+        output
+    }
+}
+
+fn read_chunks(mut bytes: &[u8]) -> Result<Vec<RiffChunk>, RmidError> {
+    let mut chunks = Vec::new();
+    while bytes.len() >= 8 {
+        let mut id = [0; 4];
+        id.copy_from_slice(&bytes[0..4]);
+        let len = (bytes[4] as usize) | (bytes[5] as usize) << 8 |
+                  (bytes[6] as usize) << 16 | (bytes[7] as usize) << 24;
+        let end = 8 + len;
+        if end > bytes.len() {
+            return Err(RmidError::Truncated);
+        }
+        chunks.push(RiffChunk { id, data: bytes[8..end].to_vec() });
+        // chunks are padded to an even length
+        bytes = &bytes[(end + len % 2).min(bytes.len())..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk(output: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    let len = data.len() as u32;
+    output.extend_from_slice(id);
+    output.extend_from_slice(&[len as u8, (len >> 8) as u8, (len >> 16) as u8, (len >> 24) as u8]);
+    output.extend_from_slice(data);
+    if len % 2 == 1 {
+        output.push(0);
+    }
+}
+
+fn read_info(bytes: &[u8]) -> Result<RmidInfo, RmidError> {
+    let mut info = RmidInfo::default();
+    for chunk in read_chunks(bytes)? {
+        let text = || {
+            let end = chunk.data.iter().position(|b| *b == 0).unwrap_or(chunk.data.len());
+            Some(String::from_utf8_lossy(&chunk.data[..end]).into_owned())
+        };
+        match &chunk.id {
+            b"INAM" => info.title = text(),
+            b"ICOP" => info.copyright = text(),
+            b"ICMT" => info.comments = text(),
+            _ => info.other.push(chunk)
+        }
+    }
+    Ok(info)
+}
+
+fn write_info(info: &RmidInfo) -> Vec<u8> {
+    let mut output = Vec::new();
+    output.extend_from_slice(b"INFO");
+    let fields = [(b"INAM", &info.title), (b"ICOP", &info.copyright), (b"ICMT", &info.comments)];
+    for &(id, text) in &fields {
+        if let Some(ref text) = *text {
+            // text is stored zero terminated
+            let mut data = text.as_bytes().to_vec();
+            data.push(0);
+            write_chunk(&mut output, id, &data);
+        }
+    }
+    for chunk in &info.other {
+        write_chunk(&mut output, &chunk.id, &chunk.data);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMF: &[u8] = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60\
+                         MTrk\x00\x00\x00\x04\x00\xff\x2f\x00";
+
+    #[test]
+    fn test_read() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF\x4c\x00\x00\x00RMID");
+        bytes.extend_from_slice(b"data\x1a\x00\x00\x00");
+        bytes.extend_from_slice(SMF);
+        bytes.extend_from_slice(b"LIST\x1c\x00\x00\x00INFO");
+        bytes.extend_from_slice(b"INAM\x04\x00\x00\x00Tune");
+        bytes.extend_from_slice(b"IENG\x03\x00\x00\x00Sam\x00");
+        bytes.extend_from_slice(b"abcd\x01\x00\x00\x00\x2a\x00");
+        let rmid = Rmid::read(&bytes).unwrap();
+        assert_eq!(rmid.smf, SMF);
+        assert_eq!(rmid.info.title, Some("Tune".to_string()));
+        assert_eq!(rmid.info.copyright, None);
+        assert_eq!(rmid.info.other, vec![RiffChunk { id: *b"IENG", data: b"Sam".to_vec() }]);
+        assert_eq!(rmid.dls, None);
+        assert_eq!(rmid.other, vec![RiffChunk { id: *b"abcd", data: vec![42] }]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rmid = Rmid::new(SMF.to_vec());
+        rmid.info.title = Some("Tune".to_string());
+        rmid.info.copyright = Some("(c) 2015".to_string());
+        rmid.info.comments = Some("odd".to_string());
+        rmid.dls = Some(b"DLS colh\x04\x00\x00\x00\x00\x00\x00\x00".to_vec());
+        rmid.other.push(RiffChunk { id: *b"abcd", data: vec![1, 2, 3] });
+
+        let bytes = rmid.to_bytes();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes.len() % 2, 0);
+        assert_eq!(Rmid::read(&bytes), Ok(rmid));
+    }
+
+    #[test]
+    fn test_repeated_chunks() {
+        let info = b"LIST\x10\x00\x00\x00INFOINAM\x04\x00\x00\x00Tune";
+        let dls = b"RIFF\x04\x00\x00\x00DLS ";
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF\x6e\x00\x00\x00RMID");
+        bytes.extend_from_slice(b"data\x1a\x00\x00\x00");
+        bytes.extend_from_slice(SMF);
+        for _ in 0..2 {
+            bytes.extend_from_slice(info);
+            bytes.extend_from_slice(dls);
+        }
+        let rmid = Rmid::read(&bytes).unwrap();
+        assert_eq!(rmid.info.title, Some("Tune".to_string()));
+        assert_eq!(rmid.dls, Some(b"DLS ".to_vec()));
+        assert_eq!(rmid.other, vec![RiffChunk { id: *b"LIST", data: info[8..].to_vec() },
+                                    RiffChunk { id: *b"RIFF", data: dls[8..].to_vec() }]);
+        assert_eq!(Rmid::read(&rmid.to_bytes()), Ok(rmid));
+    }
+
+    #[test]
+    fn test_smf() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF\x26\x00\x00\x00RMID");
+        bytes.extend_from_slice(b"data\x1a\x00\x00\x00");
+        bytes.extend_from_slice(SMF);
+        let rmid = Rmid::read(&bytes).unwrap();
+        let smf = rmid.smf().unwrap();
+        assert_eq!(smf, Smf::read(SMF).unwrap());
+        assert_eq!(smf.tracks.len(), 1);
+        assert_eq!(Rmid::from_smf(&smf), Ok(rmid));
+        assert_eq!(Rmid::new(b"MThd".to_vec()).smf(), Err(SmfError::NotSmf));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Rmid::read(b"MThd\x00\x00\x00\x06"), Err(RmidError::NotRiff));
+        assert_eq!(Rmid::read(b"RIFF\x04\x00\x00\x00WAVE"), Err(RmidError::NotRmid));
+        assert_eq!(Rmid::read(b"RIFF\x04\x00\x00\x00RMID"), Err(RmidError::MissingData));
+        assert_eq!(Rmid::read(b"RIFF\x0c\x00\x00\x00RMIDdata\x10\x00\x00\x00MThd"),
+                   Err(RmidError::Truncated));
+    }
+}
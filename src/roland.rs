@@ -0,0 +1,110 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::U7;
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::SysEx;
+use utils::mask7;
+
+/// Roland's manufacturer ID
+pub const ROLAND: Manufacturer = Manufacturer::OneByte(0x41);
+
+/// The model ID used by all GS devices
+pub const GS_MODEL: U7 = 0x42;
+
+/// The default device ID of Roland devices
+pub const ROLAND_DEFAULT_DEVICE: U7 = 0x10;
+
+const RQ1: U7 = 0x11;
+const DT1: U7 = 0x12;
+
+/// The Roland checksum of an address and data, the value that makes the sum of them all a
+/// multiple of 128
+pub fn roland_checksum(bytes: &[U7]) -> U7 {
+    let sum = bytes.iter().fold(0u32, |sum, b| sum + mask7(*b) as u32);
+    ((128 - sum % 128) % 128) as U7
+}
+
+/// Data Set 1 (DT1), sets the parameters starting at `address` to `data`
+///
+/// The length of `model` and `address` depends on the device, GS devices have a 1 byte model
+/// (`GS_MODEL`) and 3 byte addresses.
+pub fn roland_dt1(device: U7, model: &[U7], address: &[U7], data: &[U7]) -> Message {
+    roland_message(device, model, DT1, address, data)
+}
+
+/// Data Request 1 (RQ1), requests `size` bytes of parameters from `address`, the device replies
+/// with a DT1
+pub fn roland_rq1(device: U7, model: &[U7], address: &[U7], size: &[U7]) -> Message {
+    roland_message(device, model, RQ1, address, size)
+}
+
+/// GS Reset, resets a GS device to its GS defaults
+pub fn gs_reset(device: U7) -> Message {
+    roland_dt1(device, &[GS_MODEL], &[0x40, 0x00, 0x7F], &[0x00])
+}
+
+/// Returns `true` if `message` is a Roland DT1 or RQ1 for `model` with a correct checksum
+pub fn roland_checksum_ok(message: &Message, model: &[U7]) -> bool {
+    match *message {
+        SysEx(ROLAND, ref data) if data.len() > model.len() + 2 => {
+            let (header, body) = data.split_at(model.len() + 2);
+            let (body, checksum) = body.split_at(body.len() - 1);
+            &header[1..model.len() + 1] == model &&
+                (header[model.len() + 1] == DT1 || header[model.len() + 1] == RQ1) &&
+                roland_checksum(body) == checksum[0]
+        },
+        _ => false
+    }
+}
+
+fn roland_message(device: U7, model: &[U7], command: U7, address: &[U7], body: &[U7]) -> Message {
+    let mut data = vec![mask7(device)];
+    data.extend(model.iter().map(|b| mask7(*b)));
+    data.push(command);
+    let start = data.len();
+    data.extend(address.iter().chain(body).map(|b| mask7(*b)));
+    let checksum = roland_checksum(&data[start..]);
+    data.push(checksum);
+    SysEx(ROLAND, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gs_reset() {
+        // F0 41 10 42 12 40 00 7F 00 41 F7
+        assert_eq!(gs_reset(ROLAND_DEFAULT_DEVICE),
+                   SysEx(ROLAND, vec![0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41]));
+    }
+
+    #[test]
+    fn test_checksum() {
+        // GS master volume, F0 41 10 42 12 40 00 04 7F 3D F7
+        let message = roland_dt1(0x10, &[GS_MODEL], &[0x40, 0x00, 0x04], &[0x7F]);
+        assert_eq!(message, SysEx(ROLAND, vec![0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x7F, 0x3D]));
+        assert!(roland_checksum_ok(&message, &[GS_MODEL]));
+        assert!(!roland_checksum_ok(&message, &[0x16]));
+
+        let bad = SysEx(ROLAND, vec![0x10, 0x42, 0x12, 0x40, 0x00, 0x04, 0x7F, 0x3E]);
+        assert!(!roland_checksum_ok(&bad, &[GS_MODEL]));
+        assert_eq!(roland_checksum(&[]), 0);
+        assert_eq!(roland_checksum(&[0x40, 0x00, 0x00]), 0x40);
+    }
+
+    #[test]
+    fn test_rq1() {
+        // a 4 byte model ID, as used by newer devices
+        let message = roland_rq1(0x10, &[0x00, 0x00, 0x00, 0x41], &[0x18, 0x00, 0x00, 0x00],
+                                 &[0x00, 0x00, 0x00, 0x10]);
+        assert_eq!(message, SysEx(ROLAND, vec![0x10, 0x00, 0x00, 0x00, 0x41, 0x11,
+                                               0x18, 0x00, 0x00, 0x00,
+                                               0x00, 0x00, 0x00, 0x10, 0x58]));
+        assert!(roland_checksum_ok(&message, &[0x00, 0x00, 0x00, 0x41]));
+    }
+}
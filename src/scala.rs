@@ -0,0 +1,481 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use constants::PITCH_BEND_CENTER;
+use types::{U7, U14};
+use note::Note;
+use tuning::{Hz, TuningPitch, SingleNoteTuning, BulkTuningDump};
+
+/// An error from parsing a Scala `.scl` or `.kbm` file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseScalaError {
+    /// The line number of the error, starting from 1
+    pub line: usize,
+    pub reason: String
+}
+
+impl fmt::Display for ParseScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for ParseScalaError {}
+
+/// A scale from a Scala `.scl` file
+///
+/// The pitches are in cents above the first degree of the scale (which is not listed), the last
+/// pitch is the period the scale repeats at, usually an octave (1200 cents).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaScale {
+    pub description: String,
+    pub pitches: Vec<f64>
+}
+
+impl ScalaScale {
+    /// Parse the contents of a `.scl` file
+    ///
+    /// ```text
+    /// ! meantone.scl
+    /// Quarter-comma meantone, 5 notes
+    /// 5
+    /// 193.157
+    /// 5/4
+    /// 696.578
+    /// 889.735
+    /// 2
+    /// ```
+    ///
+    /// Pitches containing a `.` are in cents, any others are ratios, either `a/b` or a whole
+    /// number. Lines starting with `!` are ignored, as is anything after a pitch.
+    pub fn from_scl(text: &str) -> Result<ScalaScale, ParseScalaError> {
+        let (mut lines, end) = (lines(text), last_line(text));
+        // the description is the only line that can be blank
+        let description = lines.next().map_or("", |l| l.1).to_owned();
+        let (line, count) = next_value(&mut lines, end, "number of notes",
+                                       |s| s.parse::<usize>().ok())?;
+        if count == 0 {
+            return Err(ParseScalaError { line, reason: "scale has no notes".to_owned() });
+        }
+        let mut pitches = Vec::with_capacity(count);
+        for _ in 0..count {
+            pitches.push(next_value(&mut lines, end, "pitch", parse_pitch)?.1);
+        }
+        Ok(ScalaScale { description, pitches })
+    }
+
+    /// The number of degrees in one period of the scale
+    pub fn len(&self) -> usize {
+        self.pitches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pitches.is_empty()
+    }
+
+    /// The pitch of a scale degree in cents above degree 0, degrees outside of the first period
+    /// are repeated at the period, returns `None` if the scale is empty
+    pub fn degree_cents(&self, degree: i64) -> Option<f64> {
+        let period_cents = *self.pitches.last()?;
+        let n = self.len() as i64;
+        let (period, degree) = (degree.div_euclid(n), degree.rem_euclid(n) as usize);
+        Some(period as f64 * period_cents +
+             if degree == 0 { 0.0 } else { self.pitches[degree - 1] })
+    }
+}
+
+/// A keyboard mapping from a Scala `.kbm` file, which maps Midi notes to scale degrees
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// The first note to retune
+    pub first: Note,
+    /// The last note to retune
+    pub last: Note,
+    /// The note that plays degree 0 of the scale
+    pub middle: Note,
+    /// The note tuned to `frequency`
+    pub reference: Note,
+    /// The frequency of `reference` in Hz
+    pub frequency: f64,
+    /// The scale degree each mapping pattern repeats at, 0 for the period of the scale
+    pub octave_degree: usize,
+    /// The scale degree played by each note in a repeating pattern starting at `middle`, `None`
+    /// for notes that are not played, an empty mapping plays each degree in turn
+    pub mapping: Vec<Option<usize>>
+}
+
+/// Each note plays the next degree of the scale, with degree 0 on middle C and A4 at 440Hz
+impl Default for KeyboardMapping {
+    fn default() -> KeyboardMapping {
+        KeyboardMapping {
+            first: Note(0),
+            last: Note(127),
+            middle: Note(60),
+            reference: Note(69),
+            frequency: 440.0,
+            octave_degree: 0,
+            mapping: vec![]
+        }
+    }
+}
+
+impl KeyboardMapping {
+    /// Parse the contents of a `.kbm` file
+    ///
+    /// ```text
+    /// ! size, first note, last note, middle note, reference note, frequency, octave degree
+    /// 12
+    /// 0
+    /// 127
+    /// 60
+    /// 69
+    /// 440.0
+    /// 12
+    /// ! the mapping, x for notes that are not played
+    /// 0
+    /// x
+    /// 2
+    /// ...
+    /// ```
+    ///
+    /// Lines starting with `!` are ignored, as is anything after a value. Missing mapping entries
+    /// are not played.
+    pub fn from_kbm(text: &str) -> Result<KeyboardMapping, ParseScalaError> {
+        let (mut lines, end) = (lines(text).filter(|l| !l.1.is_empty()), last_line(text));
+        let note = |s: &str| s.parse::<U7>().ok().filter(|n| *n < 128).map(Note);
+        let (_, size) = next_value(&mut lines, end, "map size", |s| s.parse::<usize>().ok())?;
+        let (_, first) = next_value(&mut lines, end, "first note", note)?;
+        let (_, last) = next_value(&mut lines, end, "last note", note)?;
+        let (_, middle) = next_value(&mut lines, end, "middle note", note)?;
+        let (line, reference) = next_value(&mut lines, end, "reference note", note)?;
+        let (_, frequency) = next_value(&mut lines, end, "reference frequency", |s| {
+            s.parse::<f64>().ok().filter(|f| *f > 0.0 && f.is_finite())
+        })?;
+        let (_, octave_degree) = next_value(&mut lines, end, "octave degree",
+                                            |s| s.parse::<usize>().ok())?;
+        let mut mapping = Vec::with_capacity(size);
+        for (line, entry) in lines.take(size) {
+            match entry.split_whitespace().next() {
+                Some("x") | Some("X") => mapping.push(None),
+                Some(degree) => match degree.parse() {
+                    Ok(degree) => mapping.push(Some(degree)),
+                    Err(_) => return Err(ParseScalaError {
+                        line,
+                        reason: format!("invalid mapping entry '{}'", degree)
+                    })
+                },
+                None => mapping.push(None)
+            }
+        }
+        mapping.resize(size, None);
+        let mapping = KeyboardMapping {
+            first, last, middle, reference, frequency, octave_degree, mapping
+        };
+        if mapping.degree(reference).is_none() {
+            return Err(ParseScalaError { line, reason: "reference note is not mapped".to_owned() });
+        }
+        Ok(mapping)
+    }
+
+    // the repeat of the mapping pattern and the scale degree played by `note`, `None` if the note
+    // is not mapped, ignores `first` and `last`
+    fn degree(&self, note: Note) -> Option<(i64, i64)> {
+        let offset = note.0 as i64 - self.middle.0 as i64;
+        if self.mapping.is_empty() {
+            return Some((0, offset));
+        }
+        let size = self.mapping.len() as i64;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        Some((offset.div_euclid(size), degree as i64))
+    }
+
+    // cents above degree 0 on `middle`
+    fn cents(&self, scale: &ScalaScale, note: Note) -> Option<f64> {
+        let (pattern, degree) = self.degree(note)?;
+        let octave_degree = if self.octave_degree == 0 { scale.len() } else { self.octave_degree };
+        let octave = scale.degree_cents(octave_degree as i64)?;
+        Some(pattern as f64 * octave + scale.degree_cents(degree)?)
+    }
+}
+
+/// The frequencies of the 128 Midi notes in a tuning, `None` for notes that are not retuned
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuningTable {
+    pub frequencies: Vec<Option<f64>>
+}
+
+impl TuningTable {
+    /// The tuning of `scale` mapped to notes by `mapping`, no notes are retuned if `scale` is empty
+    pub fn new(scale: &ScalaScale, mapping: &KeyboardMapping) -> TuningTable {
+        let reference = mapping.cents(scale, mapping.reference).unwrap_or(0.0);
+        let frequencies = (0..128).map(|n| {
+            if n < mapping.first.0 || n > mapping.last.0 {
+                return None;
+            }
+            mapping.cents(scale, Note(n))
+                .map(|cents| mapping.frequency * ((cents - reference) / 1200.0).exp2())
+        });
+        TuningTable { frequencies: frequencies.collect() }
+    }
+
+    /// The tuning as a Midi Tuning Standard bulk dump, notes that are not retuned or out of range
+    /// are sent as no change
+    pub fn to_bulk_dump(&self, device: U7, program: U7, name: &str) -> BulkTuningDump {
+        BulkTuningDump {
+            device,
+            program,
+            name: name.to_owned(),
+            pitches: self.pitches().into_iter().map(|p| p.map(|p| p.1)).collect()
+        }
+    }
+
+    /// The tuning as Midi Tuning Standard single note tuning changes, each message holds at most
+    /// 127 changes
+    pub fn to_single_note_tunings(&self, device: U7, bank: Option<U7>, program: U7)
+                                  -> Vec<SingleNoteTuning> {
+        let changes = self.pitches().into_iter().flatten().collect::<Vec<_>>();
+        changes.chunks(127)
+            .map(|changes| SingleNoteTuning {
+                device,
+                real_time: true,
+                bank,
+                program,
+                changes: changes.to_vec()
+            })
+            .collect()
+    }
+
+    /// The tuning as the note to play and the `PitchBend` to send with it, for each note, for
+    /// synths without Midi Tuning Standard support
+    ///
+    /// `bend_range` is the pitch bend sensitivity of the synth in semitones. As pitch bend applies
+    /// to a whole channel, each note needs a channel of its own. Notes that are not retuned, or
+    /// can't be reached with the bend range, are `None`.
+    pub fn pitch_bends(&self, bend_range: U7) -> Vec<Option<(Note, U14)>> {
+        self.frequencies.iter().map(|f| {
+            let semitones = 69.0 + 12.0 * (f.as_ref()? / 440.0).log2();
+            let note = semitones.round().clamp(0.0, 127.0);
+            let bend = PITCH_BEND_CENTER as f64 +
+                ((semitones - note) * PITCH_BEND_CENTER as f64 / bend_range as f64).round();
+            if bend_range > 0 && (0.0..16384.0).contains(&bend) {
+                Some((Note(note as U7), bend as U14))
+            }
+            else {
+                None
+            }
+        }).collect()
+    }
+
+    fn pitches(&self) -> Vec<Option<(Note, TuningPitch)>> {
+        self.frequencies.iter().enumerate().map(|(n, f)| {
+            f.and_then(|f| TuningPitch::from_frequency(Hz(f))).map(|p| (Note(n as U7), p))
+        }).collect()
+    }
+}
+
+// the non-comment lines with their line numbers
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|l| !l.1.starts_with('!'))
+}
+
+// the number of the last line, where a missing value is reported
+fn last_line(text: &str) -> usize {
+    text.lines().count().max(1)
+}
+
+fn next_value<'a, T, I, F>(lines: &mut I, last: usize, name: &str, parse: F)
+                          -> Result<(usize, T), ParseScalaError>
+    where I: Iterator<Item = (usize, &'a str)>, F: Fn(&str) -> Option<T> {
+    let (line, text) = lines.find(|l| !l.1.is_empty()).ok_or_else(|| ParseScalaError {
+        line: last,
+        reason: format!("missing {}", name)
+    })?;
+    let value = text.split_whitespace().next().unwrap_or("");
+    match parse(value) {
+        Some(value) => Ok((line, value)),
+        None => Err(ParseScalaError { line, reason: format!("invalid {} '{}'", name, value) })
+    }
+}
+
+// a pitch in cents, from cents or a ratio
+fn parse_pitch(text: &str) -> Option<f64> {
+    if text.contains('.') {
+        return f64::from_str(text).ok().filter(|c| c.is_finite());
+    }
+    let (numerator, denominator) = match text.find('/') {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None => (text, "1")
+    };
+    let numerator = numerator.parse::<u64>().ok()?;
+    let denominator = denominator.parse::<u64>().ok()?;
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    Some(1200.0 * (numerator as f64 / denominator as f64).log2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEANTONE: &str = "! meantone.scl
+!
+Quarter-comma meantone, 5 notes
+ 5
+!
+ 193.157
+ 5/4    major third
+ 696.578
+ 889.735
+ 2/1
+";
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    fn equal_temperament() -> ScalaScale {
+        ScalaScale {
+            description: "".to_owned(),
+            pitches: (1..13).map(|i| i as f64 * 100.0).collect()
+        }
+    }
+
+    #[test]
+    fn test_scl() {
+        let scale = ScalaScale::from_scl(MEANTONE).unwrap();
+        assert_eq!(scale.description, "Quarter-comma meantone, 5 notes");
+        assert_eq!(scale.len(), 5);
+        assert_eq!(scale.pitches[0], 193.157);
+        assert_close(scale.pitches[1], 386.313714);
+        assert_eq!(scale.pitches[4], 1200.0);
+        assert_eq!(scale.degree_cents(0), Some(0.0));
+        assert_eq!(scale.degree_cents(6), Some(1393.157));
+        assert_eq!(scale.degree_cents(-2), Some(696.578 - 1200.0));
+
+        let empty = ScalaScale { description: String::new(), pitches: vec![] };
+        assert_eq!(empty.degree_cents(1), None);
+        let table = TuningTable::new(&empty, &KeyboardMapping::default());
+        assert!(table.frequencies.iter().all(|f| f.is_none()));
+
+        let blank = ScalaScale::from_scl("\n1\n3\n").unwrap();
+        assert_eq!(blank.description, "");
+        assert_close(blank.pitches[0], 1901.955001);
+    }
+
+    #[test]
+    fn test_scl_errors() {
+        let error = ScalaScale::from_scl("scale\n2\n100.0\n3/0\n").unwrap_err();
+        assert_eq!(error, ParseScalaError { line: 4, reason: "invalid pitch '3/0'".to_owned() });
+        let error = ScalaScale::from_scl("scale\n2\n100.0\n").unwrap_err();
+        assert_eq!(error, ParseScalaError { line: 3, reason: "missing pitch".to_owned() });
+        assert_eq!(ScalaScale::from_scl("").unwrap_err().line, 1);
+        let error = ScalaScale::from_scl("scale\nfive\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid number of notes 'five'");
+        assert!(ScalaScale::from_scl("scale\n0\n").is_err());
+        assert!(ScalaScale::from_scl("scale\n1\n-3/2\n").is_err());
+    }
+
+    #[test]
+    fn test_kbm() {
+        let text = "! white keys only
+7
+0
+127
+60
+69
+440.0
+12
+0
+x
+2
+x
+4
+5
+x
+";
+        let mapping = KeyboardMapping::from_kbm(text).unwrap();
+        assert_eq!(mapping.mapping, vec![Some(0), None, Some(2), None, Some(4), Some(5), None]);
+        assert_eq!(mapping.octave_degree, 12);
+        assert_eq!(mapping.degree(Note(60)), Some((0, 0)));
+        assert_eq!(mapping.degree(Note(61)), None);
+        assert_eq!(mapping.degree(Note(67)), Some((1, 0)));
+        assert_eq!(mapping.degree(Note(59)), None);
+        assert_eq!(mapping.degree(Note(58)), Some((-1, 5)));
+
+        let error = KeyboardMapping::from_kbm("7\n0\n127\n60\n61\n440.0\n12\n0\nx\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 5: reference note is not mapped");
+        let error = KeyboardMapping::from_kbm("0\n0\n128\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: invalid last note '128'");
+    }
+
+    #[test]
+    fn test_table() {
+        let table = TuningTable::new(&equal_temperament(), &KeyboardMapping::default());
+        assert_eq!(table.frequencies.len(), 128);
+        assert_close(table.frequencies[69].unwrap(), 440.0);
+        assert_close(table.frequencies[60].unwrap(), 261.625565);
+        assert_close(table.frequencies[0].unwrap(), 8.175799);
+
+        // meantone, with a 5 note keyboard and 1/1 at 261.6Hz
+        let mapping = KeyboardMapping {
+            first: Note(50),
+            last: Note(70),
+            reference: Note(60),
+            frequency: 261.6,
+            ..KeyboardMapping::default()
+        };
+        let table = TuningTable::new(&ScalaScale::from_scl(MEANTONE).unwrap(), &mapping);
+        assert_eq!(table.frequencies[49], None);
+        assert_close(table.frequencies[60].unwrap(), 261.6);
+        assert_close(table.frequencies[62].unwrap(), 327.0);
+        assert_close(table.frequencies[65].unwrap(), 523.2);
+        assert_close(table.frequencies[55].unwrap(), 130.8);
+        assert_eq!(table.frequencies[71], None);
+    }
+
+    #[test]
+    fn test_mts() {
+        let table = TuningTable::new(&equal_temperament(), &KeyboardMapping::default());
+        let dump = table.to_bulk_dump(0, 1, "12-TET");
+        assert_eq!(dump.pitches[69], Some(TuningPitch::new(Note(69), 0)));
+        assert_eq!(dump.pitches.iter().filter(|p| p.is_some()).count(), 128);
+
+        let tunings = table.to_single_note_tunings(0x7F, None, 1);
+        assert_eq!(tunings.len(), 2);
+        assert_eq!(tunings[0].changes.len(), 127);
+        assert_eq!(tunings[1].changes, vec![(Note(127), TuningPitch::new(Note(127), 0))]);
+    }
+
+    #[test]
+    fn test_pitch_bends() {
+        let eighth_tones = ScalaScale {
+            description: "".to_owned(),
+            pitches: (1..49).map(|i| i as f64 * 25.0).collect()
+        };
+        let mapping = KeyboardMapping {
+            reference: Note(60),
+            frequency: 261.6255653,
+            ..KeyboardMapping::default()
+        };
+        let table = TuningTable::new(&eighth_tones, &mapping);
+        let bends = table.pitch_bends(2);
+        assert_eq!(bends[60], Some((Note(60), 8192)));
+        assert_eq!(bends[61], Some((Note(60), 9216)));
+        // three eighth tones above middle C, rounded up to C#
+        assert_eq!(bends[63], Some((Note(61), 7168)));
+        assert_eq!(bends[64], Some((Note(61), 8192)));
+        assert_eq!(bends[0], Some((Note(45), 8192)));
+        assert_eq!(bends[127], Some((Note(77), 7168)));
+        assert_eq!(table.pitch_bends(1)[61], Some((Note(60), 10240)));
+        assert_eq!(table.pitch_bends(0)[60], None);
+    }
+}
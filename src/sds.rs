@@ -0,0 +1,631 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use constants::UNIVERSAL_NON_REAL_TIME;
+use types::{U7, U14};
+use message::Message;
+use universal::{UniversalSysEx, NonRealTime};
+use utils::{mask7, msb_lsb_to_u14, u14_to_msb_lsb};
+
+/// The number of sample bytes in a data packet
+pub const SDS_PACKET_LENGTH: usize = 120;
+
+/// The largest period, length or loop point of a Sample Dump Header, which are sent as 21 bits
+pub const SDS_MAX_VALUE: u32 = 0x1FFFFF;
+
+/// How long a sender waits for a reply to the header before sending without handshakes
+const HEADER_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a sender waits for a reply to a packet before sending the next
+const PACKET_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// How a sample loops while it is sustained
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LoopType {
+    Forward,
+    /// Forwards then backwards
+    Alternating,
+    Off
+}
+
+/// The Sample Dump Header, describes a sample before its data packets
+///
+/// The sample methods need a valid `bits`, see `is_valid`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SampleDumpHeader {
+    pub device: U7,
+    /// The number of the sample in the sampler
+    pub sample: U14,
+    /// The bits per sample, from 8 to 28
+    pub bits: U7,
+    /// The sample period in nanoseconds, e.g. 22675 for 44.1kHz
+    pub period: u32,
+    /// The length in samples
+    pub length: u32,
+    /// The first sample of the sustain loop
+    pub loop_start: u32,
+    /// The last sample of the sustain loop
+    pub loop_end: u32,
+    pub loop_type: LoopType
+}
+
+impl SampleDumpHeader {
+    /// Read a Sample Dump Header message, returns `None` if the message is not one
+    pub fn from_message(message: &Message) -> Option<SampleDumpHeader> {
+        let (device, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, NonRealTime::SampleDumpHeader, data) => {
+                (device, data)
+            },
+            _ => return None
+        };
+        match data[..] {
+            [sl, sh, bits, ref rest @ ..] if rest.len() == 13 && (8..=28).contains(&bits) => {
+                let loop_type = match rest[12] {
+                    0x00 => LoopType::Forward,
+                    0x01 => LoopType::Alternating,
+                    0x7F => LoopType::Off,
+                    _ => return None
+                };
+                Some(SampleDumpHeader {
+                    device,
+                    sample: msb_lsb_to_u14(sh, sl),
+                    bits,
+                    period: from_u21(&rest[0..3]),
+                    length: from_u21(&rest[3..6]),
+                    loop_start: from_u21(&rest[6..9]),
+                    loop_end: from_u21(&rest[9..12]),
+                    loop_type
+                })
+            },
+            _ => None
+        }
+    }
+
+    /// Returns `true` if `bits` is from 8 to 28, and the period, length and loop points are at
+    /// most `SDS_MAX_VALUE`
+    pub fn is_valid(&self) -> bool {
+        (8..=28).contains(&self.bits) &&
+            [self.period, self.length, self.loop_start, self.loop_end].iter()
+                .all(|v| *v <= SDS_MAX_VALUE)
+    }
+
+    /// The message, returns `None` if the header is not valid
+    pub fn to_message(&self) -> Option<Message> {
+        if !self.is_valid() {
+            return None;
+        }
+        let (sh, sl) = u14_to_msb_lsb(self.sample);
+        let mut data = vec![sl, sh, self.bits];
+        for value in &[self.period, self.length, self.loop_start, self.loop_end] {
+            data.extend_from_slice(&to_u21(*value));
+        }
+        data.push(match self.loop_type {
+            LoopType::Forward => 0x00,
+            LoopType::Alternating => 0x01,
+            LoopType::Off => 0x7F
+        });
+        Some(UniversalSysEx::NonRealTime(self.device, NonRealTime::SampleDumpHeader, data)
+            .to_message())
+    }
+
+    /// The number of bytes used to send each sample
+    pub fn bytes_per_sample(&self) -> usize {
+        (self.bits as usize).div_ceil(7)
+    }
+
+    /// The number of samples in each data packet
+    pub fn samples_per_packet(&self) -> usize {
+        SDS_PACKET_LENGTH / self.bytes_per_sample()
+    }
+
+    /// The number of data packets needed to send the sample
+    pub fn packets(&self) -> usize {
+        (self.length as usize).div_ceil(self.samples_per_packet())
+    }
+
+    /// Encode signed samples as the bytes of data packets, each sample is left justified in
+    /// `bytes_per_sample` bytes, offset so that 0 is the most negative value
+    pub fn encode_samples(&self, samples: &[i32]) -> Vec<Vec<U7>> {
+        let bytes = self.bytes_per_sample();
+        let shift = bytes * 7 - self.bits as usize;
+        let offset = 1i64 << (self.bits - 1);
+        samples.chunks(self.samples_per_packet()).map(|chunk| {
+            let mut packet = Vec::with_capacity(SDS_PACKET_LENGTH);
+            for sample in chunk {
+                let value = ((*sample as i64 + offset).clamp(0, 2 * offset - 1) as u64) << shift;
+                packet.extend((0..bytes).rev().map(|i| (value >> (i * 7)) as U7 & 0x7F));
+            }
+            packet.resize(SDS_PACKET_LENGTH, 0);
+            packet
+        }).collect()
+    }
+
+    /// Decode the bytes of data packets to `length` signed samples
+    pub fn decode_samples(&self, data: &[U7]) -> Vec<i32> {
+        let bytes = self.bytes_per_sample();
+        let shift = bytes * 7 - self.bits as usize;
+        let offset = 1i64 << (self.bits - 1);
+        let samples = data.chunks(SDS_PACKET_LENGTH)
+            .flat_map(|packet| packet.chunks(bytes).filter(|s| s.len() == bytes))
+            .map(|s| {
+                let value = s.iter().fold(0u64, |value, b| value << 7 | mask7(*b) as u64);
+                ((value >> shift) as i64 - offset) as i32
+            });
+        samples.take(self.length as usize).collect()
+    }
+}
+
+/// A Sample Data Packet, the second argument is the packet number (counting from 0, wrapping
+/// around at 128) and the third is the `SDS_PACKET_LENGTH` bytes of sample data
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SampleDataPacket(pub U7, pub U7, pub Vec<U7>);
+
+impl SampleDataPacket {
+    /// Read a Sample Data Packet message, returns `None` if the message is not one or the checksum
+    /// is wrong
+    pub fn from_message(message: &Message) -> Option<SampleDataPacket> {
+        match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, NonRealTime::SampleDataPacket, data) => {
+                let (checksum, body) = data.split_last()?;
+                if body.len() == SDS_PACKET_LENGTH + 1 &&
+                    packet_checksum(device, body) == *checksum {
+                    Some(SampleDataPacket(device, body[0], body[1..].to_vec()))
+                }
+                else {
+                    None
+                }
+            },
+            _ => None
+        }
+    }
+
+    /// The message, the data is padded or truncated to `SDS_PACKET_LENGTH` bytes
+    pub fn to_message(&self) -> Message {
+        let SampleDataPacket(device, number, ref samples) = *self;
+        let mut data = vec![mask7(number)];
+        data.extend(samples.iter().map(|b| mask7(*b)));
+        data.resize(SDS_PACKET_LENGTH + 1, 0);
+        let checksum = packet_checksum(mask7(device), &data);
+        data.push(checksum);
+        UniversalSysEx::NonRealTime(device, NonRealTime::SampleDataPacket, data).to_message()
+    }
+}
+
+// the XOR of 0x7E, the device, the sub-ID, the packet number and the data
+fn packet_checksum(device: U7, data: &[U7]) -> U7 {
+    let start = UNIVERSAL_NON_REAL_TIME ^ device ^ NonRealTime::SampleDataPacket.to_u7();
+    data.iter().fold(start, |checksum, b| checksum ^ b) & 0x7F
+}
+
+/// The Sample Dump Request message, asks `device` to send a sample
+pub fn sample_dump_request(device: U7, sample: U14) -> Message {
+    let (sh, sl) = u14_to_msb_lsb(sample);
+    UniversalSysEx::NonRealTime(device, NonRealTime::SampleDumpRequest, vec![sl, sh]).to_message()
+}
+
+/// A handshake message, sent by the receiver of a dump in reply to each packet, with the packet
+/// number (0 for the header)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Handshake {
+    /// The packet was received, send the next
+    Ack(U7),
+    /// The packet was not received correctly, send it again
+    Nak(U7),
+    /// Stop the dump
+    Cancel(U7),
+    /// Wait until the next handshake before sending anything else
+    Wait(U7)
+}
+
+impl Handshake {
+    /// Read a handshake message, as the device ID and the handshake, returns `None` if the message
+    /// is not one
+    pub fn from_message(message: &Message) -> Option<(U7, Handshake)> {
+        match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, id, data) => {
+                let packet = match data[..] {
+                    [packet] => packet,
+                    _ => return None
+                };
+                let handshake = match id {
+                    NonRealTime::Ack => Handshake::Ack(packet),
+                    NonRealTime::Nak => Handshake::Nak(packet),
+                    NonRealTime::Cancel => Handshake::Cancel(packet),
+                    NonRealTime::Wait => Handshake::Wait(packet),
+                    _ => return None
+                };
+                Some((device, handshake))
+            },
+            _ => None
+        }
+    }
+
+    pub fn to_message(&self, device: U7) -> Message {
+        let (id, packet) = match *self {
+            Handshake::Ack(packet) => (NonRealTime::Ack, packet),
+            Handshake::Nak(packet) => (NonRealTime::Nak, packet),
+            Handshake::Cancel(packet) => (NonRealTime::Cancel, packet),
+            Handshake::Wait(packet) => (NonRealTime::Wait, packet)
+        };
+        UniversalSysEx::NonRealTime(device, id, vec![mask7(packet)]).to_message()
+    }
+}
+
+/// Sends a sample dump, following the handshakes from the receiver
+///
+/// If the receiver does not reply to the header within 2 seconds, or to a packet within 20ms,
+/// the dump carries on without handshakes. Times are given by the caller, as the time since any
+/// fixed point.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SdsSender {
+    header: SampleDumpHeader,
+    packets: Vec<Vec<U7>>,
+    /// 0 for the header, then 1 for the first packet, etc
+    next: usize,
+    /// When the current message was sent, `None` if it hasn't been
+    sent: Option<Duration>,
+    waiting: bool,
+    cancelled: bool
+}
+
+impl SdsSender {
+    /// A sender for `samples`, the length in `header` is set from them, returns `None` if the
+    /// header is not valid or there are more than `SDS_MAX_VALUE` samples
+    pub fn new(header: SampleDumpHeader, samples: &[i32]) -> Option<SdsSender> {
+        if samples.len() > SDS_MAX_VALUE as usize {
+            return None;
+        }
+        let header = SampleDumpHeader { length: samples.len() as u32, ..header };
+        if !header.is_valid() {
+            return None;
+        }
+        let packets = header.encode_samples(samples);
+        Some(SdsSender { header, packets, next: 0, sent: None, waiting: false, cancelled: false })
+    }
+
+    /// Record a message from the receiver, other messages are ignored
+    pub fn receive(&mut self, message: &Message) {
+        let handshake = match Handshake::from_message(message) {
+            Some((device, h)) if device == self.header.device && !self.is_finished() => h,
+            _ => return
+        };
+        match handshake {
+            Handshake::Ack(packet) if packet == self.packet_number() => {
+                self.next += 1;
+                self.sent = None;
+                self.waiting = false;
+            },
+            Handshake::Nak(packet) if packet == self.packet_number() => {
+                self.sent = None;
+                self.waiting = false;
+            },
+            Handshake::Cancel(_) => self.cancelled = true,
+            Handshake::Wait(_) => self.waiting = true,
+            _ => ()
+        }
+    }
+
+    /// The next message to send at `now`, if any
+    pub fn poll(&mut self, now: Duration) -> Option<Message> {
+        if self.is_finished() || self.waiting {
+            return None;
+        }
+        if let Some(sent) = self.sent {
+            let timeout = if self.next == 0 { HEADER_TIMEOUT } else { PACKET_TIMEOUT };
+            if now < sent + timeout {
+                return None;
+            }
+            // no reply, carry on without handshakes
+            self.next += 1;
+            if self.is_finished() {
+                return None;
+            }
+        }
+        self.sent = Some(now);
+        match self.next {
+            0 => self.header.to_message(),
+            next => {
+                let packet = SampleDataPacket(self.header.device, self.packet_number(),
+                                              self.packets[next - 1].clone());
+                Some(packet.to_message())
+            }
+        }
+    }
+
+    /// Returns `true` once every packet has been sent, or the receiver has cancelled
+    pub fn is_finished(&self) -> bool {
+        self.cancelled || self.next > self.packets.len()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    fn packet_number(&self) -> U7 {
+        match self.next {
+            0 => 0,
+            next => ((next - 1) % 128) as U7
+        }
+    }
+}
+
+/// Receives a sample dump, replying with handshakes
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SdsReceiver {
+    device: U7,
+    header: Option<SampleDumpHeader>,
+    data: Vec<U7>,
+    /// The number of packets received
+    packets: usize
+}
+
+impl SdsReceiver {
+    /// A receiver for dumps to `device`
+    pub fn new(device: U7) -> SdsReceiver {
+        SdsReceiver { device, header: None, data: vec![], packets: 0 }
+    }
+
+    /// Record a message from the sender, returns the handshake to reply with
+    ///
+    /// A header starts a new dump. Packets with a bad checksum are replied to with a NAK, and
+    /// packets that are sent again are only kept once.
+    pub fn receive(&mut self, message: &Message) -> Option<Message> {
+        if let Some(header) = SampleDumpHeader::from_message(message) {
+            if header.device != self.device {
+                return None;
+            }
+            self.header = Some(header);
+            self.data.clear();
+            self.packets = 0;
+            return Some(Handshake::Ack(0).to_message(self.device));
+        }
+        let number = match UniversalSysEx::from_message(message) {
+            Some(UniversalSysEx::NonRealTime(device, NonRealTime::SampleDataPacket, ref data))
+                if device == self.device && !data.is_empty() && self.header.is_some() => data[0],
+            _ => return None
+        };
+        let handshake = match SampleDataPacket::from_message(message) {
+            Some(SampleDataPacket(_, number, data)) => {
+                if number == (self.packets % 128) as U7 && !self.is_complete() {
+                    self.data.extend(data);
+                    self.packets += 1;
+                    Handshake::Ack(number)
+                }
+                else if number == ((self.packets + 127) % 128) as U7 {
+                    Handshake::Ack(number)
+                }
+                else {
+                    Handshake::Nak(number)
+                }
+            },
+            None => Handshake::Nak(number)
+        };
+        Some(handshake.to_message(self.device))
+    }
+
+    /// Cancel the dump, returns the handshake to send
+    pub fn cancel(&mut self) -> Message {
+        self.header = None;
+        Handshake::Cancel((self.packets % 128) as U7).to_message(self.device)
+    }
+
+    /// The header of the current dump
+    pub fn header(&self) -> Option<&SampleDumpHeader> {
+        self.header.as_ref()
+    }
+
+    /// Returns `true` once all the packets of the current dump have been received
+    pub fn is_complete(&self) -> bool {
+        self.header.is_some_and(|h| self.packets >= h.packets())
+    }
+
+    /// The samples of the current dump, `None` until it is complete
+    pub fn samples(&self) -> Option<Vec<i32>> {
+        match self.header {
+            Some(ref header) if self.is_complete() => Some(header.decode_samples(&self.data)),
+            _ => None
+        }
+    }
+}
+
+fn to_u21(value: u32) -> [U7; 3] {
+    [mask7(value as U7), mask7((value >> 7) as U7), mask7((value >> 14) as U7)]
+}
+
+fn from_u21(bytes: &[U7]) -> u32 {
+    bytes.iter().rev().fold(0, |value, b| value << 7 | mask7(*b) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manufacturer::Manufacturer::*;
+    use Message::SysEx;
+
+    fn header() -> SampleDumpHeader {
+        SampleDumpHeader {
+            device: 0x00,
+            sample: 5,
+            bits: 16,
+            period: 22675,
+            length: 0,
+            loop_start: 0,
+            loop_end: 0,
+            loop_type: LoopType::Off
+        }
+    }
+
+    fn samples() -> Vec<i32> {
+        (0..100).map(|i| (i * 997 % 65536) - 32768).collect()
+    }
+
+    // run a dump between a sender and receiver, `link` can change or drop each message
+    fn loopback<F>(sender: &mut SdsSender, receiver: &mut SdsReceiver, mut link: F) -> usize
+        where F: FnMut(Message) -> Option<Message> {
+        let mut now = Duration::from_secs(0);
+        let mut sent = 0;
+        while !sender.is_finished() {
+            if let Some(message) = sender.poll(now) {
+                sent += 1;
+                if let Some(reply) = link(message).and_then(|m| receiver.receive(&m)) {
+                    sender.receive(&reply);
+                }
+            }
+            now += Duration::from_millis(1);
+        }
+        sent
+    }
+
+    #[test]
+    fn test_header() {
+        let header = SampleDumpHeader { length: 100000, loop_start: 10, loop_end: 99999,
+                                        loop_type: LoopType::Forward, ..header() };
+        let message = header.to_message().unwrap();
+        assert_eq!(message, SysEx(OneByte(0x7E), vec![0x00, 0x01, 0x05, 0x00, 0x10,
+                                                      0x13, 0x31, 0x01,
+                                                      0x20, 0x0D, 0x06,
+                                                      0x0A, 0x00, 0x00,
+                                                      0x1F, 0x0D, 0x06,
+                                                      0x00]));
+        assert_eq!(SampleDumpHeader::from_message(&message), Some(header));
+        assert_eq!(header.bytes_per_sample(), 3);
+        assert_eq!(header.samples_per_packet(), 40);
+        assert_eq!(header.packets(), 2500);
+        assert_eq!(sample_dump_request(0x00, 5),
+                   SysEx(OneByte(0x7E), vec![0x00, 0x03, 0x05, 0x00]));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        for &bits in &[0, 7, 29] {
+            let header = SampleDumpHeader { bits, ..header() };
+            assert!(!header.is_valid());
+            assert_eq!(header.to_message(), None);
+            assert_eq!(SdsSender::new(header, &samples()), None);
+        }
+        let long_loop = SampleDumpHeader { loop_end: SDS_MAX_VALUE + 1, ..header() };
+        assert_eq!(long_loop.to_message(), None);
+        assert_eq!(SdsSender::new(long_loop, &[]), None);
+        let slow = SampleDumpHeader { period: SDS_MAX_VALUE, ..header() };
+        assert!(slow.to_message().is_some());
+
+        // too many samples for the length
+        let samples = vec![0; SDS_MAX_VALUE as usize + 1];
+        assert_eq!(SdsSender::new(header(), &samples), None);
+        assert!(SdsSender::new(header(), &samples[1..]).is_some());
+    }
+
+    #[test]
+    fn test_packet() {
+        let packet = SampleDataPacket(0x01, 0x7F, vec![0x7F; SDS_PACKET_LENGTH]);
+        let message = packet.to_message();
+        match message {
+            SysEx(OneByte(0x7E), ref data) => {
+                assert_eq!(data.len(), 2 + 1 + SDS_PACKET_LENGTH + 1);
+                // the packet number is 0x7F and the 120 bytes of data cancel out
+                assert_eq!(data[data.len() - 1], 0x7E ^ 0x01 ^ 0x02 ^ 0x7F);
+            },
+            _ => panic!()
+        }
+        assert_eq!(SampleDataPacket::from_message(&message), Some(packet));
+        assert_eq!(SampleDataPacket::from_message(&corrupt(message)), None);
+    }
+
+    fn corrupt(message: Message) -> Message {
+        match message {
+            SysEx(manufacturer, mut data) => {
+                data[10] ^= 1;
+                SysEx(manufacturer, data)
+            },
+            message => message
+        }
+    }
+
+    #[test]
+    fn test_samples() {
+        let header = SampleDumpHeader { length: 3, bits: 12, ..header() };
+        let packets = header.encode_samples(&[-2048, 0, 2047]);
+        assert_eq!(packets.len(), 1);
+        // 12 bits is left justified in 2 bytes
+        assert_eq!(&packets[0][..6], &[0x00, 0x00, 0x40, 0x00, 0x7F, 0x7C]);
+        assert_eq!(header.decode_samples(&packets[0]), vec![-2048, 0, 2047]);
+    }
+
+    #[test]
+    fn test_handshakes() {
+        for handshake in &[Handshake::Ack(3), Handshake::Nak(4), Handshake::Cancel(5),
+                           Handshake::Wait(6)] {
+            assert_eq!(Handshake::from_message(&handshake.to_message(0x10)),
+                       Some((0x10, *handshake)));
+        }
+        assert_eq!(Handshake::Ack(0).to_message(0x10),
+                   SysEx(OneByte(0x7E), vec![0x10, 0x7F, 0x00]));
+        assert_eq!(Handshake::from_message(&sample_dump_request(0x10, 1)), None);
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut sender = SdsSender::new(header(), &samples()).unwrap();
+        let mut receiver = SdsReceiver::new(0x00);
+        assert_eq!(loopback(&mut sender, &mut receiver, Some), 4);
+        assert!(receiver.is_complete() && !sender.is_cancelled());
+        assert_eq!(receiver.header().unwrap().length, 100);
+        assert_eq!(receiver.samples(), Some(samples()));
+    }
+
+    #[test]
+    fn test_nak() {
+        let mut sender = SdsSender::new(header(), &samples()).unwrap();
+        let mut receiver = SdsReceiver::new(0x00);
+        let mut count = 0;
+        // corrupt the second packet the first time it is sent
+        let sent = loopback(&mut sender, &mut receiver, |message| {
+            count += 1;
+            Some(if count == 3 { corrupt(message) } else { message })
+        });
+        assert_eq!(sent, 5);
+        assert_eq!(receiver.samples(), Some(samples()));
+    }
+
+    #[test]
+    fn test_open_loop() {
+        let mut sender = SdsSender::new(header(), &samples()).unwrap();
+        let mut receiver = SdsReceiver::new(0x00);
+        // the replies are lost, so the sender times out on every message
+        let mut messages = vec![];
+        let mut now = Duration::from_secs(0);
+        while !sender.is_finished() {
+            if let Some(message) = sender.poll(now) {
+                messages.push(now);
+                receiver.receive(&message);
+            }
+            now += Duration::from_millis(1);
+        }
+        assert_eq!(messages, vec![Duration::from_millis(0), Duration::from_millis(2000),
+                                  Duration::from_millis(2020), Duration::from_millis(2040)]);
+        assert_eq!(receiver.samples(), Some(samples()));
+    }
+
+    #[test]
+    fn test_wait_and_cancel() {
+        let mut sender = SdsSender::new(header(), &samples()).unwrap();
+        let mut receiver = SdsReceiver::new(0x00);
+        let now = Duration::from_secs(0);
+        let header = sender.poll(now).unwrap();
+        sender.receive(&Handshake::Wait(0).to_message(0x00));
+        assert_eq!(sender.poll(now + Duration::from_secs(10)), None);
+        sender.receive(&receiver.receive(&header).unwrap());
+        assert!(sender.poll(now + Duration::from_secs(10)).is_some());
+
+        // handshakes for other devices are ignored
+        sender.receive(&Handshake::Cancel(1).to_message(0x01));
+        assert!(!sender.is_finished());
+        sender.receive(&receiver.cancel());
+        assert!(sender.is_finished() && sender.is_cancelled());
+        assert_eq!(sender.poll(now + Duration::from_secs(20)), None);
+        assert_eq!(receiver.header(), None);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+
+use message::Message;
+use Message::ActiveSensing;
+
+/// Watches incoming messages for the Active Sensing timeout
+///
+/// Nothing is timed until the first `ActiveSensing` arrives, after that any message resets the
+/// timer. Times are given by the caller, as the time since any fixed point, e.g. when the
+/// connection was opened.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SensingWatchdog {
+    timeout: Duration,
+    last: Option<Duration>
+}
+
+impl SensingWatchdog {
+    /// A watchdog with the 300ms timeout from the spec
+    pub fn new() -> SensingWatchdog {
+        SensingWatchdog::with_timeout(Duration::from_millis(300))
+    }
+
+    pub fn with_timeout(timeout: Duration) -> SensingWatchdog {
+        SensingWatchdog { timeout, last: None }
+    }
+
+    /// Record a message arriving at `now`
+    pub fn receive(&mut self, now: Duration, message: &Message) {
+        if self.last.is_some() || *message == ActiveSensing {
+            self.last = Some(now);
+        }
+    }
+
+    /// Returns `true` if the connection has been lost at `now`
+    ///
+    /// When the connection is lost the caller should turn off all voices (e.g. with
+    /// `NoteGuard::silence`). The watchdog then returns to normal operation and will not time out
+    /// again until another `ActiveSensing` arrives.
+    pub fn check(&mut self, now: Duration) -> bool {
+        match self.last {
+            Some(last) if now > last + self.timeout => {
+                self.last = None;
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// Returns `true` if `ActiveSensing` has been received and the connection is being timed
+    pub fn is_active(&self) -> bool {
+        self.last.is_some()
+    }
+}
+
+impl Default for SensingWatchdog {
+    fn default() -> SensingWatchdog {
+        SensingWatchdog::new()
+    }
+}
+
+/// Decides when to send `ActiveSensing`, so that a receiver never waits longer than its timeout
+///
+/// `ActiveSensing` is only needed when nothing else has been sent within the interval. Times are
+/// given by the caller, as for `SensingWatchdog`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SensingSender {
+    interval: Duration,
+    last: Option<Duration>
+}
+
+impl SensingSender {
+    /// A sender with an interval of 250ms, leaving a margin within the 300ms timeout
+    pub fn new() -> SensingSender {
+        SensingSender::with_interval(Duration::from_millis(250))
+    }
+
+    pub fn with_interval(interval: Duration) -> SensingSender {
+        SensingSender { interval, last: None }
+    }
+
+    /// Record a message being sent at `now`
+    pub fn send(&mut self, now: Duration) {
+        self.last = Some(now);
+    }
+
+    /// Returns `ActiveSensing` if it should be sent at `now`, and records it as sent
+    pub fn poll(&mut self, now: Duration) -> Option<Message> {
+        match self.last {
+            Some(last) if now < last + self.interval => None,
+            _ => {
+                self.last = Some(now);
+                Some(ActiveSensing)
+            }
+        }
+    }
+}
+
+impl Default for SensingSender {
+    fn default() -> SensingSender {
+        SensingSender::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Channel::*;
+    use Message::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let mut w = SensingWatchdog::new();
+        w.receive(ms(0), &NoteOn(Ch1, 60, 100));
+        assert!(!w.is_active());
+        assert!(!w.check(ms(1000)));
+
+        w.receive(ms(1000), &ActiveSensing);
+        w.receive(ms(1200), &TimingClock);
+        assert!(w.is_active());
+        assert!(!w.check(ms(1500)));
+        assert!(w.check(ms(1501)));
+
+        // back to normal operation
+        assert!(!w.is_active());
+        assert!(!w.check(ms(5000)));
+    }
+
+    #[test]
+    fn test_sender() {
+        let mut s = SensingSender::new();
+        assert_eq!(s.poll(ms(0)), Some(ActiveSensing));
+        assert_eq!(s.poll(ms(249)), None);
+        s.send(ms(200));
+        assert_eq!(s.poll(ms(449)), None);
+        assert_eq!(s.poll(ms(450)), Some(ActiveSensing));
+    }
+
+    #[test]
+    fn test_sender_watchdog() {
+        let mut s = SensingSender::new();
+        let mut w = SensingWatchdog::new();
+        for t in 0..100 {
+            let now = ms(t * 10);
+            if let Some(message) = s.poll(now) {
+                w.receive(now, &message);
+            }
+            assert!(!w.check(now));
+        }
+        assert!(w.check(ms(1300)));
+    }
+}
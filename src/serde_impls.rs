@@ -0,0 +1,389 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+// `Serialize` and `Deserialize` implementations for the types that can't simply be derived
+//
+// Channels are written as the numbers 1-16, notes as their names (e.g. "C4"), one byte
+// manufacturers as a number and three byte manufacturers as an array, and messages as a map with
+// a `type` and named fields.
+
+use std::fmt;
+
+use num::FromPrimitive;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor, SeqAccess, Unexpected};
+
+use types::{Channel, U7, U14};
+use manufacturer::Manufacturer;
+use message::Message;
+use note::Note;
+use track::{Event, Track};
+
+impl Serialize for Channel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8 + 1)
+    }
+}
+
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Channel, D::Error> {
+        let n = u8::deserialize(deserializer)?;
+        n.checked_sub(1).and_then(Channel::from_u8).ok_or_else(|| {
+            de::Error::invalid_value(Unexpected::Unsigned(n as u64), &"a channel from 1 to 16")
+        })
+    }
+}
+
+impl Serialize for Note {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Note {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Note, D::Error> {
+        deserializer.deserialize_any(NoteVisitor)
+    }
+}
+
+// accepts a note name or a note number
+struct NoteVisitor;
+
+impl<'de> Visitor<'de> for NoteVisitor {
+    type Value = Note;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a note name or a note number from 0 to 127")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Note, E> {
+        s.parse().map_err(|_| E::invalid_value(Unexpected::Str(s), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Note, E> {
+        if n < 128 {
+            Ok(Note(n as U7))
+        }
+        else {
+            Err(E::invalid_value(Unexpected::Unsigned(n), &self))
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Note, E> {
+        if n >= 0 {
+            self.visit_u64(n as u64)
+        }
+        else {
+            Err(E::invalid_value(Unexpected::Signed(n), &self))
+        }
+    }
+}
+
+impl Serialize for Manufacturer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Manufacturer::OneByte(b) => serializer.serialize_u8(b),
+            Manufacturer::ThreeByte(b1, b2, b3) => [b1, b2, b3].serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Manufacturer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Manufacturer, D::Error> {
+        deserializer.deserialize_any(ManufacturerVisitor)
+    }
+}
+
+// accepts a single byte, or an array of three bytes
+struct ManufacturerVisitor;
+
+impl<'de> Visitor<'de> for ManufacturerVisitor {
+    type Value = Manufacturer;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a manufacturer ID byte, or an array of 3 bytes")
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Manufacturer, E> {
+        if n < 256 {
+            Ok(Manufacturer::OneByte(n as U7))
+        }
+        else {
+            Err(E::invalid_value(Unexpected::Unsigned(n), &self))
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Manufacturer, E> {
+        if n >= 0 {
+            self.visit_u64(n as u64)
+        }
+        else {
+            Err(E::invalid_value(Unexpected::Signed(n), &self))
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Manufacturer, A::Error> {
+        let mut next = |i| seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &self));
+        let b1 = next(0)?;
+        let b2 = next(1)?;
+        let b3 = next(2)?;
+        if seq.next_element::<U7>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
+        Ok(Manufacturer::ThreeByte(b1, b2, b3))
+    }
+}
+
+impl Serialize for Track {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.events().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Track {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Track, D::Error> {
+        Vec::<Event>::deserialize(deserializer).map(Track::from_events)
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MessageDef::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Message, D::Error> {
+        MessageDef::deserialize(deserializer).map(Message::from)
+    }
+}
+
+// `Message` with named fields
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessageDef {
+    MtcQuarterFrame { piece: U7, value: U7 },
+    SongPositionPointer { position: U14 },
+    Start,
+    TimingClock,
+    Continue,
+    Stop,
+    ActiveSensing,
+    SystemReset,
+    AllSoundOff { channel: Channel },
+    ResetAllControllers { channel: Channel },
+    LocalControlOff { channel: Channel },
+    LocalControlOn { channel: Channel },
+    AllNotesOff { channel: Channel },
+    NoteOff { channel: Channel, note: U7, velocity: U7 },
+    ProgramChange { channel: Channel, program: U7 },
+    ControlChange { channel: Channel, controller: U7, value: U7 },
+    #[serde(rename = "rpn7")]
+    RPN7 { channel: Channel, rpn: U14, value: U7 },
+    #[serde(rename = "rpn14")]
+    RPN14 { channel: Channel, rpn: U14, value: U14 },
+    #[serde(rename = "nrpn7")]
+    NRPN7 { channel: Channel, nrpn: U14, value: U7 },
+    #[serde(rename = "nrpn14")]
+    NRPN14 { channel: Channel, nrpn: U14, value: U14 },
+    #[serde(rename = "sysex")]
+    SysEx { manufacturer: Manufacturer, data: Vec<U7> },
+    NoteOn { channel: Channel, note: U7, velocity: U7 },
+    PitchBend { channel: Channel, value: U14 },
+    PolyphonicPressure { channel: Channel, note: U7, pressure: U7 },
+    ChannelPressure { channel: Channel, pressure: U7 }
+}
+
+impl From<Message> for MessageDef {
+    fn from(message: Message) -> MessageDef {
+        match message {
+            Message::MtcQuarterFrame(piece, value) => MessageDef::MtcQuarterFrame { piece, value },
+            Message::SongPositionPointer(position) => MessageDef::SongPositionPointer { position },
+            Message::Start => MessageDef::Start,
+            Message::TimingClock => MessageDef::TimingClock,
+            Message::Continue => MessageDef::Continue,
+            Message::Stop => MessageDef::Stop,
+            Message::ActiveSensing => MessageDef::ActiveSensing,
+            Message::SystemReset => MessageDef::SystemReset,
+            Message::AllSoundOff(channel) => MessageDef::AllSoundOff { channel },
+            Message::ResetAllControllers(channel) => MessageDef::ResetAllControllers { channel },
+            Message::LocalControlOff(channel) => MessageDef::LocalControlOff { channel },
+            Message::LocalControlOn(channel) => MessageDef::LocalControlOn { channel },
+            Message::AllNotesOff(channel) => MessageDef::AllNotesOff { channel },
+            Message::NoteOff(channel, note, velocity) => {
+                MessageDef::NoteOff { channel, note, velocity }
+            },
+            Message::ProgramChange(channel, program) => {
+                MessageDef::ProgramChange { channel, program }
+            },
+            Message::ControlChange(channel, controller, value) => {
+                MessageDef::ControlChange { channel, controller, value }
+            },
+            Message::RPN7(channel, rpn, value) => MessageDef::RPN7 { channel, rpn, value },
+            Message::RPN14(channel, rpn, value) => MessageDef::RPN14 { channel, rpn, value },
+            Message::NRPN7(channel, nrpn, value) => MessageDef::NRPN7 { channel, nrpn, value },
+            Message::NRPN14(channel, nrpn, value) => MessageDef::NRPN14 { channel, nrpn, value },
+            Message::SysEx(manufacturer, data) => MessageDef::SysEx { manufacturer, data },
+            Message::NoteOn(channel, note, velocity) => {
+                MessageDef::NoteOn { channel, note, velocity }
+            },
+            Message::PitchBend(channel, value) => MessageDef::PitchBend { channel, value },
+            Message::PolyphonicPressure(channel, note, pressure) => {
+                MessageDef::PolyphonicPressure { channel, note, pressure }
+            },
+            Message::ChannelPressure(channel, pressure) => {
+                MessageDef::ChannelPressure { channel, pressure }
+            }
+        }
+    }
+}
+
+impl From<MessageDef> for Message {
+    fn from(message: MessageDef) -> Message {
+        match message {
+            MessageDef::MtcQuarterFrame { piece, value } => Message::MtcQuarterFrame(piece, value),
+            MessageDef::SongPositionPointer { position } => Message::SongPositionPointer(position),
+            MessageDef::Start => Message::Start,
+            MessageDef::TimingClock => Message::TimingClock,
+            MessageDef::Continue => Message::Continue,
+            MessageDef::Stop => Message::Stop,
+            MessageDef::ActiveSensing => Message::ActiveSensing,
+            MessageDef::SystemReset => Message::SystemReset,
+            MessageDef::AllSoundOff { channel } => Message::AllSoundOff(channel),
+            MessageDef::ResetAllControllers { channel } => Message::ResetAllControllers(channel),
+            MessageDef::LocalControlOff { channel } => Message::LocalControlOff(channel),
+            MessageDef::LocalControlOn { channel } => Message::LocalControlOn(channel),
+            MessageDef::AllNotesOff { channel } => Message::AllNotesOff(channel),
+            MessageDef::NoteOff { channel, note, velocity } => {
+                Message::NoteOff(channel, note, velocity)
+            },
+            MessageDef::ProgramChange { channel, program } => {
+                Message::ProgramChange(channel, program)
+            },
+            MessageDef::ControlChange { channel, controller, value } => {
+                Message::ControlChange(channel, controller, value)
+            },
+            MessageDef::RPN7 { channel, rpn, value } => Message::RPN7(channel, rpn, value),
+            MessageDef::RPN14 { channel, rpn, value } => Message::RPN14(channel, rpn, value),
+            MessageDef::NRPN7 { channel, nrpn, value } => Message::NRPN7(channel, nrpn, value),
+            MessageDef::NRPN14 { channel, nrpn, value } => Message::NRPN14(channel, nrpn, value),
+            MessageDef::SysEx { manufacturer, data } => Message::SysEx(manufacturer, data),
+            MessageDef::NoteOn { channel, note, velocity } => {
+                Message::NoteOn(channel, note, velocity)
+            },
+            MessageDef::PitchBend { channel, value } => Message::PitchBend(channel, value),
+            MessageDef::PolyphonicPressure { channel, note, pressure } => {
+                Message::PolyphonicPressure(channel, note, pressure)
+            },
+            MessageDef::ChannelPressure { channel, pressure } => {
+                Message::ChannelPressure(channel, pressure)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use manufacturer::Manufacturer::*;
+    use message::Message;
+    use message::Message::*;
+    use note::Note;
+    use note_span::{NoteSpan, Overlap};
+    use raw_message::RawMessage;
+    use track::{Event, Track};
+    use tuning::TuningPitch;
+    use types::Channel;
+    use types::Channel::*;
+
+    fn round_trip<T>(value: T, json: &str)
+        where T: ::serde::Serialize + ::serde::de::DeserializeOwned + PartialEq + ::std::fmt::Debug
+    {
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_channel() {
+        round_trip(Ch1, "1");
+        round_trip(Ch16, "16");
+        assert!(serde_json::from_str::<Channel>("0").is_err());
+        assert!(serde_json::from_str::<Channel>("17").is_err());
+        assert!(serde_json::from_str::<Channel>("\"Ch1\"").is_err());
+    }
+
+    #[test]
+    fn test_note() {
+        round_trip(Note(60), "\"C4\"");
+        round_trip(Note(61), "\"C#4\"");
+        assert_eq!(serde_json::from_str::<Note>("61").unwrap(), Note(61));
+        assert_eq!(serde_json::from_str::<Note>("\"Db4\"").unwrap(), Note(61));
+        assert!(serde_json::from_str::<Note>("128").is_err());
+        assert!(serde_json::from_str::<Note>("\"H4\"").is_err());
+    }
+
+    #[test]
+    fn test_manufacturer() {
+        round_trip(OneByte(0x41), "65");
+        round_trip(ThreeByte(0x00, 0x20, 0x33), "[0,32,51]");
+        assert!(serde_json::from_str::<::manufacturer::Manufacturer>("[0,32]").is_err());
+        assert!(serde_json::from_str::<::manufacturer::Manufacturer>("[0,32,51,1]").is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        round_trip(Start, r#"{"type":"start"}"#);
+        round_trip(AllNotesOff(Ch2), r#"{"type":"all_notes_off","channel":2}"#);
+        round_trip(NoteOn(Ch1, 60, 100),
+                   r#"{"type":"note_on","channel":1,"note":60,"velocity":100}"#);
+        round_trip(ControlChange(Ch10, 7, 90),
+                   r#"{"type":"control_change","channel":10,"controller":7,"value":90}"#);
+        round_trip(NRPN14(Ch1, 1000, 1001),
+                   r#"{"type":"nrpn14","channel":1,"nrpn":1000,"value":1001}"#);
+        round_trip(SysEx(OneByte(0x41), vec![1, 2]),
+                   r#"{"type":"sysex","manufacturer":65,"data":[1,2]}"#);
+        round_trip(PitchBend(Ch1, 8192), r#"{"type":"pitch_bend","channel":1,"value":8192}"#);
+        assert!(serde_json::from_str::<Message>(r#"{"type":"note_on","channel":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_raw_message() {
+        round_trip(RawMessage::StatusDataData(144, 60, 100),
+                   r#"{"status_data_data":[144,60,100]}"#);
+        round_trip(RawMessage::Raw(240), r#"{"raw":240}"#);
+    }
+
+    #[test]
+    fn test_track() {
+        let track = Track::from_deltas(vec![(0, Start), (96, Stop)]).unwrap();
+        let start = r#"{"tick":0,"message":{"type":"start"}}"#;
+        let stop = r#"{"tick":96,"message":{"type":"stop"}}"#;
+        round_trip(track.clone(), &format!("[{},{}]", start, stop));
+        let unsorted = format!("[{},{}]", stop, start);
+        assert_eq!(serde_json::from_str::<Track>(&unsorted).unwrap(), track);
+        round_trip(Event::new(1, Stop), r#"{"tick":1,"message":{"type":"stop"}}"#);
+    }
+
+    #[test]
+    fn test_note_span() {
+        let span = NoteSpan {
+            start: 0,
+            duration: 10,
+            channel: Ch1,
+            note: 60,
+            on_velocity: 100,
+            off_velocity: 0
+        };
+        round_trip(span, concat!(r#"{"start":0,"duration":10,"channel":1,"note":60,"#,
+                                 r#""on_velocity":100,"off_velocity":0}"#));
+        round_trip(Overlap::Fifo, r#""fifo""#);
+    }
+
+    #[test]
+    fn test_tuning_pitch() {
+        round_trip(TuningPitch::new(Note(69), 8192), r#"{"note":"A4","fraction":8192}"#);
+    }
+}
@@ -0,0 +1,612 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+
+use constants::*;
+use types::U7;
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::*;
+use raw_message::RawMessage;
+use to_raw_messages::ToRawMessages;
+use track::{Event, Track};
+use utils::{from_status_byte, msb_lsb_to_u14};
+
+const META: u8 = 0xFF;
+
+/// The timing of a Standard Midi File
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum Division {
+    /// Ticks per quarter note
+    Ticks(u16),
+    /// SMPTE timing, the first argument is the frames per second (24, 25, 29 or 30), the second
+    /// is the ticks per frame
+    Smpte(u8, u8)
+}
+
+/// A meta event from a Standard Midi File
+///
+/// Events with a length that doesn't match their type are kept as `Other`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum MetaEvent {
+    SequenceNumber(u16),
+    /// Text events, the first argument is the type, from `0x01` (text) to `0x09` (device name)
+    Text(u8, Vec<u8>),
+    ChannelPrefix(u8),
+    Port(u8),
+    EndOfTrack,
+    /// Microseconds per quarter note
+    Tempo(u32),
+    /// Hours (including the frame rate bits), minutes, seconds, frames and fractional frames
+    SmpteOffset(u8, u8, u8, u8, u8),
+    /// Numerator, denominator (as a power of 2), clocks per metronome click, 32nd notes per
+    /// quarter note
+    TimeSignature(u8, u8, u8, u8),
+    /// Number of sharps (positive) or flats (negative), and whether the key is minor
+    KeySignature(i8, bool),
+    SequencerSpecific(Vec<u8>),
+    /// Any other meta event, the first argument is the type
+    Other(u8, Vec<u8>)
+}
+
+/// The contents of an event in a Standard Midi File track
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "snake_case"))]
+pub enum SmfEventKind {
+    /// A channel message, the second argument is `true` if running status is used, i.e. the
+    /// status byte is omitted in the file
+    Midi(Message, bool),
+    /// An `F0` SysEx event, the data following the length (usually ending with `F7`)
+    SysEx(Vec<u8>),
+    /// An `F7` escape event, the data following the length
+    Escape(Vec<u8>),
+    Meta(MetaEvent)
+}
+
+/// An event in a Standard Midi File track, `delta` is the ticks since the previous event
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmfEvent {
+    pub delta: u32,
+    pub kind: SmfEventKind
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmfTrack {
+    pub events: Vec<SmfEvent>,
+    /// Any bytes in the `MTrk` chunk after the End of Track event
+    pub trailing: Vec<u8>
+}
+
+/// A chunk in a Standard Midi File that is not a track
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmfChunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>
+}
+
+/// A Standard Midi File
+///
+/// Reading and then writing a file gives identical bytes, as long as the file stores its
+/// variable length quantities in their shortest form, and has any non-track chunks after the
+/// tracks. Bytes after the last chunk that are too few to be a chunk are kept in `trailing`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Smf {
+    pub format: u16,
+    pub division: Division,
+    /// Any bytes in the `MThd` chunk after the standard 6
+    pub header_extra: Vec<u8>,
+    pub tracks: Vec<SmfTrack>,
+    pub other: Vec<SmfChunk>,
+    /// Any bytes after the last chunk
+    pub trailing: Vec<u8>
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SmfError {
+    /// The data does not start with an `MThd` chunk
+    NotSmf,
+    /// A chunk or event extends past the end of the data
+    Truncated,
+    /// A track event at the given offset is not valid
+    InvalidEvent(usize),
+    /// A delta time or event length is too large to write, the limit is 28 bits
+    TooLarge,
+    /// A `SmfEventKind::Midi` event holds a message that is not a single channel message
+    NotChannelMessage
+}
+
+impl fmt::Display for SmfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SmfError::NotSmf => f.write_str("not a Standard Midi File"),
+            SmfError::Truncated => f.write_str("Standard Midi File is truncated"),
+            SmfError::InvalidEvent(offset) => write!(f, "invalid track event at offset {}", offset),
+            SmfError::TooLarge => f.write_str("delta time or event length is too large"),
+            SmfError::NotChannelMessage => f.write_str("track event is not a channel message")
+        }
+    }
+}
+
+impl Error for SmfError {}
+
+impl SmfTrack {
+    /// Convert to a `Track` of the channel messages and complete SysEx messages in this track
+    pub fn to_track(&self) -> Track {
+        let mut tick = 0;
+        let mut events = Vec::new();
+        for e in &self.events {
+            tick += e.delta;
+            match e.kind {
+                SmfEventKind::Midi(ref message, _) => {
+                    events.push(Event::new(tick, message.clone()))
+                },
+                SmfEventKind::SysEx(ref data) => {
+                    if let Some(message) = sysex_message(data) {
+                        events.push(Event::new(tick, message));
+                    }
+                },
+                _ => ()
+            }
+        }
+        Track::from_events(events)
+    }
+}
+
+impl Smf {
+    /// Read a Standard Midi File
+    pub fn read(bytes: &[u8]) -> Result<Smf, SmfError> {
+        if bytes.len() < 14 || &bytes[0..4] != b"MThd" {
+            return Err(SmfError::NotSmf);
+        }
+        let header_len = read_u32(&bytes[4..8]) as usize;
+        if header_len < 6 {
+            return Err(SmfError::NotSmf);
+        }
+        let header_end = header_len.checked_add(8).filter(|end| *end <= bytes.len())
+            .ok_or(SmfError::Truncated)?;
+        let format = read_u16(&bytes[8..10]);
+        let division = if bytes[12] & 0x80 != 0 {
+            Division::Smpte((bytes[12] as i8).wrapping_neg() as u8, bytes[13])
+        }
+        else {
+            Division::Ticks(read_u16(&bytes[12..14]))
+        };
+        let mut smf = Smf {
+            format,
+            division,
+            header_extra: bytes[14..header_end].to_vec(),
+            tracks: Vec::new(),
+            other: Vec::new(),
+            trailing: Vec::new()
+        };
+
+        let mut offset = header_end;
+        while offset + 8 <= bytes.len() {
+            let len = read_u32(&bytes[offset + 4..offset + 8]) as usize;
+            let start = offset + 8;
+            let end = start.checked_add(len).filter(|end| *end <= bytes.len())
+                .ok_or(SmfError::Truncated)?;
+            if &bytes[offset..offset + 4] == b"MTrk" {
+                smf.tracks.push(read_track(bytes, start, end)?);
+            }
+            else {
+                let mut id = [0; 4];
+                id.copy_from_slice(&bytes[offset..offset + 4]);
+                smf.other.push(SmfChunk { id, data: bytes[start..end].to_vec() });
+            }
+            offset = end;
+        }
+        smf.trailing = bytes[offset..].to_vec();
+        Ok(smf)
+    }
+
+    /// Write a Standard Midi File
+    ///
+    /// Returns `SmfError::TooLarge` if a delta time or event length doesn't fit in a variable
+    /// length quantity, and `SmfError::NotChannelMessage` if a `SmfEventKind::Midi` event holds a
+    /// message that is not sent as a single channel message, e.g. `RPN7` or `TimingClock`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SmfError> {
+        let mut output = Vec::new();
+        output.extend_from_slice(b"MThd");
+        write_u32(&mut output, 6 + self.header_extra.len() as u32);
+        write_u16(&mut output, self.format);
+        write_u16(&mut output, self.tracks.len() as u16);
+        match self.division {
+            Division::Ticks(ticks) => write_u16(&mut output, ticks),
+            Division::Smpte(fps, ticks) => {
+                output.push((fps as i8).wrapping_neg() as u8);
+                output.push(ticks);
+            }
+        }
+        output.extend_from_slice(&self.header_extra);
+        for track in &self.tracks {
+            let data = write_track(track)?;
+            output.extend_from_slice(b"MTrk");
+            write_u32(&mut output, data.len() as u32);
+            output.extend(data);
+        }
+        for chunk in &self.other {
+            output.extend_from_slice(&chunk.id);
+            write_u32(&mut output, chunk.data.len() as u32);
+            output.extend_from_slice(&chunk.data);
+        }
+        output.extend_from_slice(&self.trailing);
+        Ok(output)
+    }
+}
+
+/// The status byte of `message`, if it is sent as a single channel message and so can be stored
+/// in a `SmfEventKind::Midi` event
+pub(crate) fn channel_status(message: &Message) -> Option<u8> {
+    match message.to_raw_messages()[..] {
+        [RawMessage::StatusData(s, _)] | [RawMessage::StatusDataData(s, _, _)] if s < SYSEX => {
+            Some(s)
+        },
+        _ => None
+    }
+}
+
+/// Convert the data from an `F0` SysEx event to a `Message::SysEx`, if it is a complete message
+pub(crate) fn sysex_message(data: &[u8]) -> Option<Message> {
+    match data.split_last() {
+        Some((&SYSEX_EOX, data)) if data.iter().all(|b| *b < 0x80) => {
+            Manufacturer::from_u7s(data).map(|(m, data)| SysEx(m, data.to_vec()))
+        },
+        _ => None
+    }
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn write_u16(output: &mut Vec<u8>, n: u16) {
+    output.extend_from_slice(&[(n >> 8) as u8, n as u8]);
+}
+
+fn write_u32(output: &mut Vec<u8>, n: u32) {
+    output.extend_from_slice(&[(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]);
+}
+
+/// Write a variable length quantity, `n` can be at most 28 bits
+fn write_vlq(output: &mut Vec<u8>, n: u32) -> Result<(), SmfError> {
+    if n > 0x0FFFFFFF {
+        return Err(SmfError::TooLarge);
+    }
+    let mut shift = 21;
+    while shift > 0 && n >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        output.push(0x80 | (n >> shift) as u8);
+        shift -= 7;
+    }
+    output.push(n as u8 & 0x7F);
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    end: usize
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, SmfError> {
+        if self.offset < self.end {
+            self.offset += 1;
+            Ok(self.bytes[self.offset - 1])
+        }
+        else {
+            Err(SmfError::Truncated)
+        }
+    }
+
+    fn data(&mut self, start: usize) -> Result<U7, SmfError> {
+        let b = self.byte()?;
+        if b < 0x80 { Ok(b) } else { Err(SmfError::InvalidEvent(start)) }
+    }
+
+    fn vlq(&mut self) -> Result<u32, SmfError> {
+        let mut n = 0;
+        for _ in 0..4 {
+            let b = self.byte()?;
+            n = (n << 7) | (b & 0x7F) as u32;
+            if b < 0x80 {
+                return Ok(n);
+            }
+        }
+        Err(SmfError::InvalidEvent(self.offset))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SmfError> {
+        if len <= self.end - self.offset {
+            self.offset += len;
+            Ok(&self.bytes[self.offset - len..self.offset])
+        }
+        else {
+            Err(SmfError::Truncated)
+        }
+    }
+}
+
+fn read_track(bytes: &[u8], start: usize, end: usize) -> Result<SmfTrack, SmfError> {
+    let mut r = Reader { bytes, offset: start, end };
+    let mut track = SmfTrack::default();
+    let mut running = None;
+    while r.offset < r.end {
+        let delta = r.vlq()?;
+        let event_start = r.offset;
+        let first = r.byte()?;
+        let kind = match first {
+            SYSEX => {
+                let len = r.vlq()? as usize;
+                SmfEventKind::SysEx(r.bytes(len)?.to_vec())
+            },
+            SYSEX_EOX => {
+                let len = r.vlq()? as usize;
+                SmfEventKind::Escape(r.bytes(len)?.to_vec())
+            },
+            META => {
+                let kind = r.byte()?;
+                let len = r.vlq()? as usize;
+                SmfEventKind::Meta(meta_event(kind, r.bytes(len)?))
+            },
+            0x80..=0xEF => {
+                running = Some(first);
+                let d1 = r.data(event_start)?;
+                SmfEventKind::Midi(channel_message(first, d1, &mut r, event_start)?, false)
+            },
+            0x00..=0x7F => {
+                let status = running.ok_or(SmfError::InvalidEvent(event_start))?;
+                SmfEventKind::Midi(channel_message(status, first, &mut r, event_start)?, true)
+            },
+            _ => return Err(SmfError::InvalidEvent(event_start))
+        };
+        let end_of_track = kind == SmfEventKind::Meta(MetaEvent::EndOfTrack);
+        track.events.push(SmfEvent { delta, kind });
+        if end_of_track {
+            track.trailing = bytes[r.offset..end].to_vec();
+            break;
+        }
+    }
+    Ok(track)
+}
+
+fn channel_message(status: u8, d1: U7, r: &mut Reader, start: usize) -> Result<Message, SmfError> {
+    let (status, ch) = from_status_byte(status);
+    let message = match status {
+        NOTE_OFF => NoteOff(ch, d1, r.data(start)?),
+        NOTE_ON => NoteOn(ch, d1, r.data(start)?),
+        POLYPHONIC_PRESSURE => PolyphonicPressure(ch, d1, r.data(start)?),
+        CONTROL_CHANGE => ControlChange(ch, d1, r.data(start)?),
+        PROGRAM_CHANGE => ProgramChange(ch, d1),
+        CHANNEL_PRESSURE => ChannelPressure(ch, d1),
+        _ => PitchBend(ch, msb_lsb_to_u14(r.data(start)?, d1))
+    };
+    Ok(message)
+}
+
+/// Classify a meta event from its type and data
+pub(crate) fn meta_event(kind: u8, data: &[u8]) -> MetaEvent {
+    match (kind, data) {
+        (0x00, &[msb, lsb]) => MetaEvent::SequenceNumber((msb as u16) << 8 | lsb as u16),
+        (0x01..=0x09, _) => MetaEvent::Text(kind, data.to_vec()),
+        (0x20, &[ch]) => MetaEvent::ChannelPrefix(ch),
+        (0x21, &[port]) => MetaEvent::Port(port),
+        (0x2F, &[]) => MetaEvent::EndOfTrack,
+        (0x51, &[b1, b2, b3]) => {
+            MetaEvent::Tempo((b1 as u32) << 16 | (b2 as u32) << 8 | b3 as u32)
+        },
+        (0x54, &[hr, mn, se, fr, ff]) => MetaEvent::SmpteOffset(hr, mn, se, fr, ff),
+        (0x58, &[nn, dd, cc, bb]) => MetaEvent::TimeSignature(nn, dd, cc, bb),
+        (0x59, &[sf, mi]) if mi < 2 => MetaEvent::KeySignature(sf as i8, mi == 1),
+        (0x7F, _) => MetaEvent::SequencerSpecific(data.to_vec()),
+        _ => MetaEvent::Other(kind, data.to_vec())
+    }
+}
+
+fn meta_bytes(meta: &MetaEvent) -> (u8, Vec<u8>) {
+    match *meta {
+        MetaEvent::SequenceNumber(n) => (0x00, vec!((n >> 8) as u8, n as u8)),
+        MetaEvent::Text(kind, ref text) => (kind, text.clone()),
+        MetaEvent::ChannelPrefix(ch) => (0x20, vec!(ch)),
+        MetaEvent::Port(port) => (0x21, vec!(port)),
+        MetaEvent::EndOfTrack => (0x2F, vec!()),
+        MetaEvent::Tempo(t) => (0x51, vec!((t >> 16) as u8, (t >> 8) as u8, t as u8)),
+        MetaEvent::SmpteOffset(hr, mn, se, fr, ff) => (0x54, vec!(hr, mn, se, fr, ff)),
+        MetaEvent::TimeSignature(nn, dd, cc, bb) => (0x58, vec!(nn, dd, cc, bb)),
+        MetaEvent::KeySignature(sf, minor) => (0x59, vec!(sf as u8, minor as u8)),
+        MetaEvent::SequencerSpecific(ref data) => (0x7F, data.clone()),
+        MetaEvent::Other(kind, ref data) => (kind, data.clone())
+    }
+}
+
+fn write_track(track: &SmfTrack) -> Result<Vec<u8>, SmfError> {
+    let mut output = Vec::new();
+    let mut running = None;
+    for event in &track.events {
+        write_vlq(&mut output, event.delta)?;
+        match event.kind {
+            SmfEventKind::Midi(ref message, running_status) => {
+                let status = channel_status(message).ok_or(SmfError::NotChannelMessage)?;
+                let bytes: Vec<u8> = message.to_raw_messages().iter()
+                    .flat_map(|r| r.to_bytes())
+                    .collect();
+                if running_status && running == Some(status) {
+                    output.extend_from_slice(&bytes[1..]);
+                }
+                else {
+                    output.extend(bytes);
+                }
+                running = Some(status);
+            },
+            SmfEventKind::SysEx(ref data) => {
+                output.push(SYSEX);
+                write_vlq(&mut output, data.len() as u32)?;
+                output.extend_from_slice(data);
+            },
+            SmfEventKind::Escape(ref data) => {
+                output.push(SYSEX_EOX);
+                write_vlq(&mut output, data.len() as u32)?;
+                output.extend_from_slice(data);
+            },
+            SmfEventKind::Meta(ref meta) => {
+                let (kind, data) = meta_bytes(meta);
+                output.push(META);
+                output.push(kind);
+                write_vlq(&mut output, data.len() as u32)?;
+                output.extend(data);
+            }
+        }
+    }
+    output.extend_from_slice(&track.trailing);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Channel::*;
+
+    #[test]
+    fn test_vlq() {
+        let cases: [(u32, &[u8]); 8] = [(0, &[0x00]),
+                                        (0x40, &[0x40]),
+                                        (0x7F, &[0x7F]),
+                                        (0x80, &[0x81, 0x00]),
+                                        (0x2000, &[0xC0, 0x00]),
+                                        (0x3FFF, &[0xFF, 0x7F]),
+                                        (0x4000, &[0x81, 0x80, 0x00]),
+                                        (0x0FFFFFFF, &[0xFF, 0xFF, 0xFF, 0x7F])];
+        for &(n, bytes) in &cases {
+            let mut output = Vec::new();
+            assert_eq!(write_vlq(&mut output, n), Ok(()));
+            assert_eq!(output, bytes);
+            let mut r = Reader { bytes, offset: 0, end: bytes.len() };
+            assert_eq!(r.vlq(), Ok(n));
+        }
+        assert_eq!(write_vlq(&mut Vec::new(), 0x10000000), Err(SmfError::TooLarge));
+
+        let mut smf = Smf::read(b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60\
+                                 MTrk\x00\x00\x00\x04\x00\xff\x2f\x00").unwrap();
+        smf.tracks[0].events[0].delta = 0x10000000;
+        assert_eq!(smf.to_bytes(), Err(SmfError::TooLarge));
+    }
+
+    #[test]
+    fn test_read_write() {
+        let bytes = b"MThd\x00\x00\x00\x06\x00\x01\x00\x01\x00\x60\
+                      MTrk\x00\x00\x00\x29\
+                      \x00\xff\x51\x03\x07\xa1\x20\
+                      \x00\xff\x03\x04Lead\
+                      \x00\x90\x3c\x64\
+                      \x60\x3c\x00\
+                      \x00\xf0\x04\x41\x10\x42\xf7\
+                      \x00\xe0\x00\x40\
+                      \x81\x00\xc0\x05\
+                      \x00\xff\x2f\x00\
+                      XTRA\x00\x00\x00\x01\x2a";
+        let smf = Smf::read(bytes).unwrap();
+        assert_eq!(smf.format, 1);
+        assert_eq!(smf.division, Division::Ticks(96));
+        assert_eq!(smf.tracks.len(), 1);
+        let kinds: Vec<SmfEventKind> = smf.tracks[0].events.iter()
+            .map(|e| e.kind.clone())
+            .collect();
+        assert_eq!(kinds, vec![SmfEventKind::Meta(MetaEvent::Tempo(500000)),
+                               SmfEventKind::Meta(MetaEvent::Text(3, b"Lead".to_vec())),
+                               SmfEventKind::Midi(NoteOn(Ch1, 60, 100), false),
+                               SmfEventKind::Midi(NoteOn(Ch1, 60, 0), true),
+                               SmfEventKind::SysEx(vec![0x41, 0x10, 0x42, 0xF7]),
+                               SmfEventKind::Midi(PitchBend(Ch1, 8192), false),
+                               SmfEventKind::Midi(ProgramChange(Ch1, 5), false),
+                               SmfEventKind::Meta(MetaEvent::EndOfTrack)]);
+        assert_eq!(smf.tracks[0].events[6].delta, 128);
+        assert_eq!(smf.other, vec![SmfChunk { id: *b"XTRA", data: vec![42] }]);
+        assert_eq!(smf.to_bytes(), Ok(bytes.to_vec()));
+
+        assert_eq!(smf.tracks[0].to_track().to_deltas(),
+                   vec![(0, NoteOn(Ch1, 60, 100)),
+                        (96, SysEx(Manufacturer::OneByte(0x41), vec![0x10, 0x42])),
+                        (0, NoteOn(Ch1, 60, 0)),
+                        (0, PitchBend(Ch1, 8192)),
+                        (128, ProgramChange(Ch1, 5))]);
+    }
+
+    #[test]
+    fn test_smpte_division() {
+        let bytes = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\xe7\x28\
+                      MTrk\x00\x00\x00\x04\x00\xff\x2f\x00";
+        let smf = Smf::read(bytes).unwrap();
+        assert_eq!(smf.division, Division::Smpte(25, 40));
+        assert_eq!(smf.to_bytes(), Ok(bytes.to_vec()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let header = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60".to_vec();
+        let with_track = |track: &[u8]| {
+            let mut bytes = header.clone();
+            bytes.extend_from_slice(track);
+            bytes
+        };
+        assert_eq!(Smf::read(b"RIFF\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60"),
+                   Err(SmfError::NotSmf));
+        assert_eq!(Smf::read(&with_track(b"MTrk\x00\x00\x00\x08\x00")),
+                   Err(SmfError::Truncated));
+        assert_eq!(Smf::read(&with_track(b"MTrk\x00\x00\x00\x03\x00\x90\x3c")),
+                   Err(SmfError::Truncated));
+        assert_eq!(Smf::read(&with_track(b"MTrk\x00\x00\x00\x02\x00\x3c")),
+                   Err(SmfError::InvalidEvent(23)));
+        assert_eq!(Smf::read(&with_track(b"MTrk\x00\x00\x00\x02\x00\xf8")),
+                   Err(SmfError::InvalidEvent(23)));
+        assert_eq!(Smf::read(b"MThd\x00\x00\x00\x08\x00\x00\x00\x01\x00\x60\x00"),
+                   Err(SmfError::Truncated));
+    }
+
+    #[test]
+    fn test_extra_bytes() {
+        // a longer header, and bytes after the end of track
+        let bytes = b"MThd\x00\x00\x00\x08\x00\x00\x00\x01\x00\x60\x12\x34\
+                      MTrk\x00\x00\x00\x07\x00\xff\x2f\x00\x00\x90\x3c";
+        let smf = Smf::read(bytes).unwrap();
+        assert_eq!(smf.header_extra, vec![0x12, 0x34]);
+        assert_eq!(smf.tracks[0].events.len(), 1);
+        assert_eq!(smf.tracks[0].trailing, vec![0x00, 0x90, 0x3c]);
+        assert_eq!(smf.to_bytes(), Ok(bytes.to_vec()));
+
+        // stray bytes after the last chunk
+        let bytes = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60\
+                      MTrk\x00\x00\x00\x04\x00\xff\x2f\x00\x01\x02\x03";
+        let smf = Smf::read(bytes).unwrap();
+        assert_eq!(smf.tracks.len(), 1);
+        assert_eq!(smf.trailing, vec![0x01, 0x02, 0x03]);
+        assert_eq!(smf.to_bytes(), Ok(bytes.to_vec()));
+    }
+
+    #[test]
+    fn test_not_channel_message() {
+        let mut smf = Smf::read(b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60\
+                                 MTrk\x00\x00\x00\x04\x00\xff\x2f\x00").unwrap();
+        for message in &[TimingClock, SysEx(Manufacturer::OneByte(0x41), vec![]),
+                             RPN7(Ch1, 0, 2)] {
+            smf.tracks[0].events[0].kind = SmfEventKind::Midi(message.clone(), false);
+            assert_eq!(smf.to_bytes(), Err(SmfError::NotChannelMessage));
+        }
+    }
+}
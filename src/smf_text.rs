@@ -0,0 +1,630 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+
+use message::Message;
+use smf::{Smf, SmfTrack, SmfEvent, SmfEventKind, SmfChunk, MetaEvent, Division};
+use smf::{channel_status, meta_event, sysex_message};
+use to_raw_messages::ToRawMessages;
+
+const TEXT_NAMES: [(u8, &str); 9] = [(0x01, "text"),
+                                     (0x02, "copyright"),
+                                     (0x03, "track_name"),
+                                     (0x04, "instrument_name"),
+                                     (0x05, "lyric"),
+                                     (0x06, "marker"),
+                                     (0x07, "cue_point"),
+                                     (0x08, "program_name"),
+                                     (0x09, "device_name")];
+
+/// How event times are written in the text format of a Standard Midi File
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeFormat {
+    /// Ticks since the previous event
+    Delta,
+    /// Ticks since the start of the track
+    Absolute
+}
+
+/// An error from parsing the text format of a Standard Midi File
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseSmfError {
+    /// The line number of the error, starting from 1
+    pub line: usize,
+    pub reason: String
+}
+
+impl fmt::Display for ParseSmfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for ParseSmfError {}
+
+impl Smf {
+    /// Convert to text, this can be compiled back to an identical `Smf` with `from_text`
+    ///
+    /// ```text
+    /// smf format=1 division=96 time=delta
+    /// track
+    /// 0 meta track_name "Lead"
+    /// 0 meta tempo 500000
+    /// 0 note_on ch1 C4 vel=100
+    /// 96 ~note_on ch1 C4 vel=0
+    /// 0 sysex 0x41 [10 42]
+    /// 0 meta end_of_track
+    /// end_track
+    /// ```
+    ///
+    /// Each event starts with its time, either the delta from the previous event or the absolute
+    /// time from the start of the track, as given by `time=` in the header. Channel messages use
+    /// the text format of `Message`, prefixed with `~` if running status is used. SysEx events that
+    /// hold a complete message are written as a `Message`, any others as `sysex_event [..]` and
+    /// `F7` events as `escape [..]`. Meta events without a named form and chunks that are not
+    /// tracks are written as hex. Any extra bytes at the end of the header are written as
+    /// `header_extra [..]` after the header line, any bytes after the End of Track event as
+    /// `trailing [..]` before `end_track`, and any bytes after the last chunk as `trailing [..]` at
+    /// the end. Blank lines and lines starting with `#` are ignored.
+    pub fn to_text(&self, time: TimeFormat) -> String {
+        let mut output = String::new();
+        let division = match self.division {
+            Division::Ticks(ticks) => ticks.to_string(),
+            Division::Smpte(fps, ticks) => format!("smpte:{}:{}", fps, ticks)
+        };
+        let time_name = match time {
+            TimeFormat::Delta => "delta",
+            TimeFormat::Absolute => "absolute"
+        };
+        let _ = writeln!(output, "smf format={} division={} time={}",
+                         self.format, division, time_name);
+        if !self.header_extra.is_empty() {
+            let _ = writeln!(output, "header_extra {}", HexText(&self.header_extra));
+        }
+        for track in &self.tracks {
+            output.push_str("track\n");
+            let mut tick = 0u64;
+            for event in &track.events {
+                tick += event.delta as u64;
+                let t = match time {
+                    TimeFormat::Delta => event.delta as u64,
+                    TimeFormat::Absolute => tick
+                };
+                let _ = writeln!(output, "{} {}", t, EventText(&event.kind));
+            }
+            if !track.trailing.is_empty() {
+                let _ = writeln!(output, "trailing {}", HexText(&track.trailing));
+            }
+            output.push_str("end_track\n");
+        }
+        for chunk in &self.other {
+            let _ = writeln!(output, "chunk {} {}", QuotedText(&chunk.id), HexText(&chunk.data));
+        }
+        if !self.trailing.is_empty() {
+            let _ = writeln!(output, "trailing {}", HexText(&self.trailing));
+        }
+        output
+    }
+
+    /// Compile the text produced by `to_text`
+    pub fn from_text(text: &str) -> Result<Smf, ParseSmfError> {
+        let mut lines = text.lines().enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (line_no, header) = lines.next().ok_or_else(|| error(1, "missing smf header"))?;
+        let (mut smf, time) = parse_header(header).map_err(|e| error(line_no, &e))?;
+
+        let mut track: Option<(SmfTrack, u64)> = None;
+        let mut last_line = line_no;
+        for (line_no, line) in lines {
+            last_line = line_no;
+            let result = match track.take() {
+                None => {
+                    if line == "track" {
+                        track = Some((SmfTrack::default(), 0));
+                        Ok(())
+                    }
+                    else if let Some(rest) = line.strip_prefix("chunk ") {
+                        parse_chunk(rest).map(|chunk| smf.other.push(chunk))
+                    }
+                    else if let Some(rest) = line.strip_prefix("header_extra ") {
+                        parse_hex(rest).map(|data| smf.header_extra = data)
+                    }
+                    else if let Some(rest) = line.strip_prefix("trailing ") {
+                        parse_hex(rest).map(|data| smf.trailing = data)
+                    }
+                    else {
+                        Err(format!("expected 'track' or 'chunk', found '{}'", line))
+                    }
+                },
+                Some((t, tick)) => {
+                    if line == "end_track" {
+                        smf.tracks.push(t);
+                        Ok(())
+                    }
+                    else if !t.trailing.is_empty() {
+                        Err("expected 'end_track' after trailing bytes".to_string())
+                    }
+                    else if let Some(rest) = line.strip_prefix("trailing ") {
+                        let mut t = t;
+                        parse_hex(rest).map(|data| {
+                            t.trailing = data;
+                            track = Some((t, tick));
+                        })
+                    }
+                    else {
+                        let mut t = t;
+                        parse_event(line, time, tick).map(|(event, tick)| {
+                            t.events.push(event);
+                            track = Some((t, tick));
+                        })
+                    }
+                }
+            };
+            result.map_err(|e| error(line_no, &e))?;
+        }
+        if track.is_some() {
+            return Err(error(last_line, "missing end_track"));
+        }
+        Ok(smf)
+    }
+}
+
+fn error(line: usize, reason: &str) -> ParseSmfError {
+    ParseSmfError { line, reason: reason.to_string() }
+}
+
+struct EventText<'a>(&'a SmfEventKind);
+
+impl<'a> fmt::Display for EventText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            SmfEventKind::Midi(ref message, running) => {
+                write!(f, "{}{}", if running { "~" } else { "" }, message)
+            },
+            SmfEventKind::SysEx(ref data) => match sysex_message(data) {
+                Some(message) => write!(f, "{}", message),
+                None => write!(f, "sysex_event {}", HexText(data))
+            },
+            SmfEventKind::Escape(ref data) => write!(f, "escape {}", HexText(data)),
+            SmfEventKind::Meta(ref meta) => write!(f, "meta {}", MetaText(meta))
+        }
+    }
+}
+
+struct MetaText<'a>(&'a MetaEvent);
+
+impl<'a> fmt::Display for MetaText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.0 {
+            MetaEvent::SequenceNumber(n) => write!(f, "sequence_number {}", n),
+            MetaEvent::Text(kind, ref text) => match TEXT_NAMES.iter().find(|t| t.0 == kind) {
+                Some(&(_, name)) => write!(f, "{} {}", name, QuotedText(text)),
+                None => write!(f, "0x{:02X} {}", kind, HexText(text))
+            },
+            MetaEvent::ChannelPrefix(ch) => write!(f, "channel_prefix {}", ch),
+            MetaEvent::Port(port) => write!(f, "port {}", port),
+            MetaEvent::EndOfTrack => f.write_str("end_of_track"),
+            MetaEvent::Tempo(tempo) => write!(f, "tempo {}", tempo),
+            MetaEvent::SmpteOffset(hr, mn, se, fr, ff) => {
+                write!(f, "smpte_offset {} {} {} {} {}", hr, mn, se, fr, ff)
+            },
+            MetaEvent::TimeSignature(nn, dd, cc, bb) if dd < 32 => {
+                write!(f, "time_signature {}/{} {} {}", nn, 1u32 << dd, cc, bb)
+            },
+            MetaEvent::TimeSignature(nn, dd, cc, bb) => {
+                write!(f, "0x58 {}", HexText(&[nn, dd, cc, bb]))
+            },
+            MetaEvent::KeySignature(sf, minor) => {
+                write!(f, "key_signature {} {}", sf, if minor { "minor" } else { "major" })
+            },
+            MetaEvent::SequencerSpecific(ref data) => {
+                write!(f, "sequencer_specific {}", HexText(data))
+            },
+            MetaEvent::Other(kind, ref data) => write!(f, "0x{:02X} {}", kind, HexText(data))
+        }
+    }
+}
+
+struct HexText<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for HexText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, b) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02X}", b)?;
+        }
+        f.write_str("]")
+    }
+}
+
+// bytes as a quoted string, anything other than printable ASCII is escaped as \xNN
+struct QuotedText<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for QuotedText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\"")?;
+        for &b in self.0 {
+            match b {
+                b'"' => f.write_str("\\\"")?,
+                b'\\' => f.write_str("\\\\")?,
+                0x20..=0x7E => f.write_char(b as char)?,
+                _ => write!(f, "\\x{:02X}", b)?
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+fn parse_header(line: &str) -> Result<(Smf, TimeFormat), String> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("smf") {
+        return Err("expected smf header".to_string());
+    }
+    let mut smf = Smf {
+        format: 1,
+        division: Division::Ticks(96),
+        header_extra: Vec::new(),
+        tracks: Vec::new(),
+        other: Vec::new(),
+        trailing: Vec::new()
+    };
+    let mut time = TimeFormat::Delta;
+    for word in words {
+        let invalid = || format!("invalid header field '{}'", word);
+        match word.find('=').map(|i| (&word[..i], &word[i + 1..])) {
+            Some(("format", value)) => smf.format = value.parse().map_err(|_| invalid())?,
+            Some(("division", value)) => {
+                smf.division = match value.strip_prefix("smpte:") {
+                    Some(smpte) => {
+                        let (fps, ticks) = smpte.find(':')
+                            .map(|i| (&smpte[..i], &smpte[i + 1..]))
+                            .ok_or_else(invalid)?;
+                        Division::Smpte(fps.parse().map_err(|_| invalid())?,
+                                        ticks.parse().map_err(|_| invalid())?)
+                    },
+                    None => Division::Ticks(value.parse().map_err(|_| invalid())?)
+                }
+            },
+            Some(("time", "delta")) => time = TimeFormat::Delta,
+            Some(("time", "absolute")) => time = TimeFormat::Absolute,
+            _ => return Err(invalid())
+        }
+    }
+    Ok((smf, time))
+}
+
+fn parse_chunk(s: &str) -> Result<SmfChunk, String> {
+    let (id, rest) = parse_quoted(s)?;
+    if id.len() != 4 {
+        return Err("chunk id must be 4 bytes".to_string());
+    }
+    let mut chunk = SmfChunk { id: [0; 4], data: parse_hex(rest)? };
+    chunk.id.copy_from_slice(&id);
+    Ok(chunk)
+}
+
+// returns the event and its absolute time
+fn parse_event(line: &str, time: TimeFormat, last: u64) -> Result<(SmfEvent, u64), String> {
+    let (t, rest) = split_word(line);
+    let t: u64 = t.parse().map_err(|_| format!("invalid time '{}'", t))?;
+    let tick = match time {
+        TimeFormat::Delta => last + t,
+        TimeFormat::Absolute => t
+    };
+    if tick < last || tick - last > 0x0FFFFFFF {
+        return Err(format!("time '{}' is out of range", t));
+    }
+    let (name, args) = split_word(rest);
+    let kind = match name {
+        "meta" => SmfEventKind::Meta(parse_meta(args)?),
+        "sysex_event" => SmfEventKind::SysEx(parse_hex(args)?),
+        "escape" => SmfEventKind::Escape(parse_hex(args)?),
+        _ => {
+            let (running, text) = match rest.strip_prefix('~') {
+                Some(text) => (true, text),
+                None => (false, rest)
+            };
+            let message: Message = text.parse().map_err(|e| format!("{}", e))?;
+            match message {
+                Message::SysEx(..) if !running => {
+                    // the event data is everything after the F0
+                    let data = message.to_raw_messages().iter()
+                        .flat_map(|r| r.to_bytes())
+                        .skip(1)
+                        .collect();
+                    SmfEventKind::SysEx(data)
+                },
+                _ if channel_status(&message).is_some() => SmfEventKind::Midi(message, running),
+                _ => return Err(format!("'{}' can not be stored in a track", text))
+            }
+        }
+    };
+    Ok((SmfEvent { delta: (tick - last) as u32, kind }, tick))
+}
+
+fn parse_meta(s: &str) -> Result<MetaEvent, String> {
+    let (name, args) = split_word(s);
+    let numbers = |count: usize| -> Result<Vec<i64>, String> {
+        let values = args.split_whitespace()
+            .map(|a| a.parse::<i64>().map_err(|_| format!("invalid argument '{}'", a)))
+            .collect::<Result<Vec<i64>, String>>()?;
+        if values.len() == count {
+            Ok(values)
+        }
+        else {
+            Err(format!("wrong number of arguments for meta {}", name))
+        }
+    };
+    let byte = |n: i64| if (0..256).contains(&n) { Ok(n as u8) } else { Err(range(n)) };
+
+    if let Some(&(kind, _)) = TEXT_NAMES.iter().find(|t| t.1 == name) {
+        let (text, rest) = parse_quoted(args)?;
+        if !rest.trim().is_empty() {
+            return Err(format!("unexpected argument '{}'", rest.trim()));
+        }
+        return Ok(MetaEvent::Text(kind, text));
+    }
+    let meta = match name {
+        "sequence_number" => {
+            let n = numbers(1)?[0];
+            if !(0..0x10000).contains(&n) {
+                return Err(range(n));
+            }
+            MetaEvent::SequenceNumber(n as u16)
+        },
+        "channel_prefix" => MetaEvent::ChannelPrefix(byte(numbers(1)?[0])?),
+        "port" => MetaEvent::Port(byte(numbers(1)?[0])?),
+        "end_of_track" => {
+            numbers(0)?;
+            MetaEvent::EndOfTrack
+        },
+        "tempo" => {
+            let n = numbers(1)?[0];
+            if !(0..0x1000000).contains(&n) {
+                return Err(range(n));
+            }
+            MetaEvent::Tempo(n as u32)
+        },
+        "smpte_offset" => {
+            let n = numbers(5)?;
+            MetaEvent::SmpteOffset(byte(n[0])?, byte(n[1])?, byte(n[2])?, byte(n[3])?, byte(n[4])?)
+        },
+        "time_signature" => {
+            let (signature, rest) = split_word(args);
+            let invalid = || format!("invalid time signature '{}'", signature);
+            let (nn, den) = signature.find('/')
+                .map(|i| (&signature[..i], &signature[i + 1..]))
+                .ok_or_else(invalid)?;
+            let nn = nn.parse::<u8>().map_err(|_| invalid())?;
+            let den = den.parse::<u32>().ok().filter(|d| d.is_power_of_two()).ok_or_else(invalid)?;
+            let rest: Vec<u8> = rest.split_whitespace().map(|a| a.parse::<u8>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid arguments '{}'", rest))?;
+            match rest[..] {
+                [cc, bb] => MetaEvent::TimeSignature(nn, den.trailing_zeros() as u8, cc, bb),
+                _ => return Err("wrong number of arguments for meta time_signature".to_string())
+            }
+        },
+        "key_signature" => {
+            let (sf, mode) = split_word(args);
+            let sf = sf.parse::<i8>().map_err(|_| format!("invalid argument '{}'", sf))?;
+            match mode {
+                "major" => MetaEvent::KeySignature(sf, false),
+                "minor" => MetaEvent::KeySignature(sf, true),
+                _ => return Err(format!("invalid key mode '{}'", mode))
+            }
+        },
+        "sequencer_specific" => MetaEvent::SequencerSpecific(parse_hex(args)?),
+        _ => {
+            let kind = name.strip_prefix("0x")
+                .and_then(|k| u8::from_str_radix(k, 16).ok())
+                .ok_or_else(|| format!("unknown meta event '{}'", name))?;
+            // classify in the same way as reading a file, so that compiling gives the same `Smf`
+            return Ok(meta_event(kind, &parse_hex(args)?));
+        }
+    };
+    Ok(meta)
+}
+
+fn range(n: i64) -> String {
+    format!("{} is out of range", n)
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], s[i..].trim_start()),
+        None => (s, "")
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim();
+    let inner = s.strip_prefix('[').and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("expected hex data in [], found '{}'", s))?;
+    inner.split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("invalid hex byte '{}'", b)))
+        .collect()
+}
+
+// returns the unescaped bytes and the rest of the string
+fn parse_quoted(s: &str) -> Result<(Vec<u8>, &str), String> {
+    let s = s.trim_start();
+    let invalid = || format!("invalid quoted text '{}'", s);
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return Err(invalid());
+    }
+    let mut output = Vec::new();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Ok((output, &s[i + 1..])),
+            b'\\' => {
+                match bytes.get(i + 1) {
+                    Some(&b'"') => output.push(b'"'),
+                    Some(&b'\\') => output.push(b'\\'),
+                    Some(&b'x') => {
+                        let hex = s.get(i + 2..i + 4).ok_or_else(invalid)?;
+                        output.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                        i += 2;
+                    },
+                    _ => return Err(invalid())
+                }
+                i += 2;
+            },
+            b => {
+                output.push(b);
+                i += 1;
+            }
+        }
+    }
+    Err(invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMF: &[u8] = b"MThd\x00\x00\x00\x06\x00\x01\x00\x02\x00\x60\
+                         MTrk\x00\x00\x00\x24\
+                         \x00\xff\x03\x07Lead \"\x80\
+                         \x00\xff\x51\x03\x07\xa1\x20\
+                         \x00\xff\x58\x04\x06\x03\x24\x08\
+                         \x00\xff\x59\x02\xfe\x01\
+                         \x00\xff\x2f\x00\
+                         MTrk\x00\x00\x00\x2a\
+                         \x00\x90\x3c\x64\
+                         \x60\x3c\x00\
+                         \x00\xf0\x04\x41\x10\x42\xf7\
+                         \x00\xf0\x02\x43\x10\
+                         \x00\xf7\x02\x20\xf7\
+                         \x00\xb0\x07\x5a\
+                         \x81\x00\xe0\x00\x40\
+                         \x00\xff\x0a\x01\x2a\
+                         \x00\xff\x2f\x00\
+                         XTRA\x00\x00\x00\x01\x2a";
+
+    const TEXT: &str = "smf format=1 division=96 time=delta
+track
+0 meta track_name \"Lead \\\"\\x80\"
+0 meta tempo 500000
+0 meta time_signature 6/8 36 8
+0 meta key_signature -2 minor
+0 meta end_of_track
+end_track
+track
+0 note_on ch1 C4 vel=100
+96 ~note_on ch1 C4 vel=0
+0 sysex 0x41 [10 42]
+0 sysex_event [43 10]
+0 escape [20 F7]
+0 cc ch1 volume 90
+128 pitch_bend ch1 8192
+0 meta 0x0A [2A]
+0 meta end_of_track
+end_track
+chunk \"XTRA\" [2A]
+";
+
+    #[test]
+    fn test_to_text() {
+        let smf = Smf::read(SMF).unwrap();
+        assert_eq!(smf.to_text(TimeFormat::Delta), TEXT);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let smf = Smf::read(SMF).unwrap();
+        for &time in &[TimeFormat::Delta, TimeFormat::Absolute] {
+            let text = smf.to_text(time);
+            let compiled = Smf::from_text(&text).unwrap();
+            assert_eq!(compiled, smf);
+            assert_eq!(compiled.to_bytes(), Ok(SMF.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_extra_bytes() {
+        let bytes = b"MThd\x00\x00\x00\x08\x00\x00\x00\x01\x00\x60\x12\x34\
+                      MTrk\x00\x00\x00\x06\x00\xff\x2f\x00\x00\x90\
+                      \x00\x00\x00";
+        let text = "smf format=0 division=96 time=delta
+header_extra [12 34]
+track
+0 meta end_of_track
+trailing [00 90]
+end_track
+trailing [00 00 00]
+";
+        let smf = Smf::read(bytes).unwrap();
+        assert_eq!(smf.to_text(TimeFormat::Delta), text);
+        let compiled = Smf::from_text(text).unwrap();
+        assert_eq!(compiled, smf);
+        assert_eq!(compiled.to_bytes(), Ok(bytes.to_vec()));
+    }
+
+    #[test]
+    fn test_absolute() {
+        let smf = Smf::read(SMF).unwrap();
+        let text = smf.to_text(TimeFormat::Absolute);
+        assert!(text.starts_with("smf format=1 division=96 time=absolute\n"));
+        assert!(text.contains("\n96 ~note_on ch1 C4 vel=0\n96 sysex 0x41 [10 42]\n"));
+        assert!(text.contains("\n224 pitch_bend ch1 8192\n224 meta 0x0A [2A]\n"));
+    }
+
+    #[test]
+    fn test_from_text() {
+        let text = "# a comment
+smf format=0 division=smpte:25:40
+
+track
+  0 note_on ch2 60 100
+  10 note_off ch2 C4 vel=0
+  0 meta end_of_track
+end_track
+";
+        let smf = Smf::from_text(text).unwrap();
+        assert_eq!(smf.format, 0);
+        assert_eq!(smf.division, Division::Smpte(25, 40));
+        assert_eq!(smf.tracks.len(), 1);
+        assert_eq!(smf.tracks[0].events.len(), 3);
+        assert_eq!(smf.tracks[0].events[1].delta, 10);
+    }
+
+    #[test]
+    fn test_from_text_errors() {
+        let err = |text: &str| Smf::from_text(text).unwrap_err();
+        assert_eq!(err("").line, 1);
+        assert_eq!(err("track\n").reason, "expected smf header");
+        assert_eq!(err("smf format=x\n").reason, "invalid header field 'format=x'");
+        assert_eq!(err("smf\ntrack\n0 start\nend_track\n"),
+                   ParseSmfError {
+                       line: 3,
+                       reason: "'start' can not be stored in a track".to_string()
+                   });
+        assert_eq!(err("smf\ntrack\n0 note_on ch1 C4 vel=1\n").reason, "missing end_track");
+        assert_eq!(err("smf time=absolute\ntrack\n10 stop\n5 stop\nend_track\n").line, 3);
+        assert_eq!(err("smf time=absolute\ntrack\n10 pc ch1 1\n5 pc ch1 1\nend_track\n").reason,
+                   "time '5' is out of range");
+        assert_eq!(err("smf\ntrack\n0 meta tempo\nend_track\n").reason,
+                   "wrong number of arguments for meta tempo");
+        assert_eq!(err("smf\ntrack\n0 meta text \"abc\nend_track\n").reason,
+                   "invalid quoted text '\"abc'");
+        assert_eq!(err("smf\nfoo\n").line, 2);
+        assert_eq!(err("smf\ntrack\ntrailing [00]\n0 stop\nend_track\n").reason,
+                   "expected 'end_track' after trailing bytes");
+        assert_eq!(err("smf\ntrack\n0 rpn7 ch1 0 2\nend_track\n").reason,
+                   "'rpn7 ch1 0 2' can not be stored in a track");
+    }
+}
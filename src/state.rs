@@ -0,0 +1,632 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cmp::min;
+use std::collections::BTreeMap;
+
+use num::FromPrimitive;
+
+use constants::*;
+use types::{Channel, U7, U14};
+use message::Message;
+use Message::*;
+use utils::{mask7, mask14, msb_lsb_to_u14, u14_to_msb_lsb};
+
+// which kind of parameter the data entry controllers currently apply to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Parameter {
+    Rpn,
+    Nrpn
+}
+
+/// The state of a single Midi channel, as built up from the messages received on it
+///
+/// RPNs and NRPNs are tracked whether they arrive as `RPN7`, `RPN14`, etc, or as the individual
+/// `ControlChange`s, values are stored as 14 bits with 7 bit data entry setting the MSB. Note
+/// that the bank only changes when a `ProgramChange` is received, as per the spec.
+///
+/// Notes are sounding from their `NoteOn` until their key is released, or later if they are held
+/// by the sustain pedal or latched by the sostenuto pedal.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChannelState {
+    controllers: [U7; 128],
+    program: U7,
+    bank: U14,
+    pitch_bend: U14,
+    channel_pressure: U7,
+    poly_pressure: [U7; 128],
+    keys: [Option<U7>; 128],
+    sounding: [Option<U7>; 128],
+    latched: [bool; 128],
+    parameter: Option<Parameter>,
+    rpns: BTreeMap<U14, U14>,
+    nrpns: BTreeMap<U14, U14>,
+    local_control: bool
+}
+
+impl ChannelState {
+    /// The power-up state of a channel
+    pub fn new() -> ChannelState {
+        let mut controllers = [0; 128];
+        for (cc, value) in controllers.iter_mut().enumerate() {
+            *value = default_controller(cc as U7);
+        }
+        ChannelState {
+            controllers,
+            program: 0,
+            bank: 0,
+            pitch_bend: PITCH_BEND_CENTER,
+            channel_pressure: 0,
+            poly_pressure: [0; 128],
+            keys: [None; 128],
+            sounding: [None; 128],
+            latched: [false; 128],
+            parameter: None,
+            rpns: BTreeMap::new(),
+            nrpns: BTreeMap::new(),
+            local_control: true
+        }
+    }
+
+    /// Update the state with a message, the channel of the message is ignored
+    pub fn process(&mut self, message: &Message) {
+        match *message {
+            SystemReset => *self = ChannelState::new(),
+            AllSoundOff(_) => self.control_change(CC_ALL_SOUND_OFF, 0),
+            ResetAllControllers(_) => self.control_change(CC_RESET_ALL_CONTROLLERS, 0),
+            LocalControlOff(_) => self.control_change(CC_LOCAL_CONTROL, 0),
+            LocalControlOn(_) => self.control_change(CC_LOCAL_CONTROL, 127),
+            AllNotesOff(_) => self.control_change(CC_ALL_NOTES_OFF, 0),
+            NoteOff(_, note, _) | NoteOn(_, note, 0) => self.note_off(note),
+            NoteOn(_, note, vel) => {
+                let n = mask7(note) as usize;
+                self.keys[n] = Some(mask7(vel));
+                self.sounding[n] = Some(mask7(vel));
+            },
+            ProgramChange(_, program) => {
+                self.program = mask7(program);
+                self.bank = msb_lsb_to_u14(self.controllers[CC_BANK_SELECT_MSB as usize],
+                                           self.controllers[CC_BANK_SELECT_LSB as usize]);
+            },
+            ControlChange(_, cc, val) => self.control_change(cc, val),
+            RPN7(_, rpn, val) => {
+                self.select(CC_RPN_MSB, CC_RPN_LSB, rpn);
+                self.control_change(CC_DATA_ENTRY_MSB, val);
+            },
+            RPN14(_, rpn, val) => {
+                let (msb, lsb) = u14_to_msb_lsb(val);
+                self.select(CC_RPN_MSB, CC_RPN_LSB, rpn);
+                self.control_change(CC_DATA_ENTRY_MSB, msb);
+                self.control_change(CC_DATA_ENTRY_LSB, lsb);
+            },
+            NRPN7(_, nrpn, val) => {
+                self.select(CC_NRPN_MSB, CC_NRPN_LSB, nrpn);
+                self.control_change(CC_DATA_ENTRY_MSB, val);
+            },
+            NRPN14(_, nrpn, val) => {
+                let (msb, lsb) = u14_to_msb_lsb(val);
+                self.select(CC_NRPN_MSB, CC_NRPN_LSB, nrpn);
+                self.control_change(CC_DATA_ENTRY_MSB, msb);
+                self.control_change(CC_DATA_ENTRY_LSB, lsb);
+            },
+            PitchBend(_, bend) => self.pitch_bend = mask14(bend),
+            PolyphonicPressure(_, note, val) => {
+                self.poly_pressure[mask7(note) as usize] = mask7(val);
+            },
+            ChannelPressure(_, val) => self.channel_pressure = mask7(val),
+            MtcQuarterFrame(..) | SongPositionPointer(_) | Start | TimingClock | Continue | Stop |
+            ActiveSensing | SysEx(..) => ()
+        }
+    }
+
+    /// The last value received for a controller, or its default
+    pub fn controller(&self, cc: U7) -> U7 {
+        self.controllers[mask7(cc) as usize]
+    }
+
+    pub fn program(&self) -> U7 {
+        self.program
+    }
+
+    /// The bank selected at the last `ProgramChange`
+    pub fn bank(&self) -> U14 {
+        self.bank
+    }
+
+    pub fn pitch_bend(&self) -> U14 {
+        self.pitch_bend
+    }
+
+    pub fn channel_pressure(&self) -> U7 {
+        self.channel_pressure
+    }
+
+    pub fn poly_pressure(&self, note: U7) -> U7 {
+        self.poly_pressure[mask7(note) as usize]
+    }
+
+    /// Returns `true` if the key for `note` is down, regardless of pedals
+    pub fn is_key_down(&self, note: U7) -> bool {
+        self.keys[mask7(note) as usize].is_some()
+    }
+
+    /// The velocity of `note` if it is sounding
+    pub fn note_velocity(&self, note: U7) -> Option<U7> {
+        self.sounding[mask7(note) as usize]
+    }
+
+    /// All the sounding notes and their velocities, lowest note first
+    pub fn notes(&self) -> Vec<(U7, U7)> {
+        self.sounding.iter()
+            .enumerate()
+            .filter_map(|(n, vel)| vel.map(|v| (n as U7, v)))
+            .collect()
+    }
+
+    pub fn sustain(&self) -> bool {
+        self.controllers[CC_SUSTAIN as usize] >= 64
+    }
+
+    pub fn sostenuto(&self) -> bool {
+        self.controllers[CC_SOSTENUTO as usize] >= 64
+    }
+
+    pub fn local_control(&self) -> bool {
+        self.local_control
+    }
+
+    /// The value of an RPN, if it has been set
+    pub fn rpn(&self, rpn: U14) -> Option<U14> {
+        self.rpns.get(&rpn).cloned()
+    }
+
+    /// The value of an NRPN, if it has been set
+    pub fn nrpn(&self, nrpn: U14) -> Option<U14> {
+        self.nrpns.get(&nrpn).cloned()
+    }
+
+    /// All the RPNs that have been set, as `(rpn, value)` in order
+    pub fn rpns(&self) -> Vec<(U14, U14)> {
+        self.rpns.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    /// All the NRPNs that have been set, as `(nrpn, value)` in order
+    pub fn nrpns(&self) -> Vec<(U14, U14)> {
+        self.nrpns.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    /// The pitch bend range as `(semitones, cents)`, defaults to 2 semitones
+    pub fn pitch_bend_range(&self) -> (U7, U7) {
+        self.rpn(RPN_PITCH_BEND_SENSITIVITY).map_or((2, 0), u14_to_msb_lsb)
+    }
+
+    /// The messages needed to bring a receiver on `channel` from its power-up state to this state
+    ///
+    /// Only values that differ from the power-up state are sent. Bank select and program change
+    /// come first, as the bank only changes with the program, followed by the other messages
+    /// sorted by the ordering of `Message`. If any RPNs or NRPNs are sent, the null RPN is selected
+    /// at the end. Sounding notes are not included.
+    pub fn chase(&self, channel: Channel) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let (bank_msb, bank_lsb) = u14_to_msb_lsb(self.bank);
+        if self.bank != 0 {
+            messages.push(ControlChange(channel, CC_BANK_SELECT_MSB, bank_msb));
+            messages.push(ControlChange(channel, CC_BANK_SELECT_LSB, bank_lsb));
+        }
+        if self.bank != 0 || self.program != 0 {
+            messages.push(ProgramChange(channel, self.program));
+        }
+
+        let mut rest = Vec::new();
+        if !self.local_control {
+            rest.push(LocalControlOff(channel));
+        }
+        for cc in 0..120 {
+            let value = self.controllers[cc as usize];
+            let sent = match cc {
+                // a bank select that arrived after the last program change
+                CC_BANK_SELECT_MSB => bank_msb,
+                CC_BANK_SELECT_LSB => bank_lsb,
+                // covered by the RPNs and NRPNs
+                CC_DATA_ENTRY_MSB | CC_DATA_ENTRY_LSB | CC_DATA_INCREMENT..=CC_RPN_MSB => continue,
+                _ => default_controller(cc)
+            };
+            if value != sent {
+                rest.push(ControlChange(channel, cc, value));
+            }
+        }
+        for (&rpn, &value) in &self.rpns {
+            if default_rpn(rpn) != Some(value) {
+                rest.push(parameter_message(RPN7, RPN14, channel, rpn, value));
+            }
+        }
+        for (&nrpn, &value) in &self.nrpns {
+            rest.push(parameter_message(NRPN7, NRPN14, channel, nrpn, value));
+        }
+        if self.pitch_bend != PITCH_BEND_CENTER {
+            rest.push(PitchBend(channel, self.pitch_bend));
+        }
+        for (note, &value) in self.poly_pressure.iter().enumerate() {
+            if value != 0 {
+                rest.push(PolyphonicPressure(channel, note as U7, value));
+            }
+        }
+        if self.channel_pressure != 0 {
+            rest.push(ChannelPressure(channel, self.channel_pressure));
+        }
+        rest.sort();
+
+        let parameters = rest.iter()
+            .any(|m| matches!(*m, RPN7(..) | RPN14(..) | NRPN7(..) | NRPN14(..)));
+        messages.extend(rest);
+        if parameters {
+            messages.push(ControlChange(channel, CC_RPN_MSB, 127));
+            messages.push(ControlChange(channel, CC_RPN_LSB, 127));
+        }
+        messages
+    }
+
+    fn control_change(&mut self, cc: U7, val: U7) {
+        let cc = mask7(cc);
+        let val = mask7(val);
+        match cc {
+            CC_ALL_SOUND_OFF => {
+                self.keys = [None; 128];
+                self.sounding = [None; 128];
+                self.latched = [false; 128];
+            },
+            CC_RESET_ALL_CONTROLLERS => self.reset_controllers(),
+            CC_LOCAL_CONTROL => self.local_control = val >= 64,
+            CC_ALL_NOTES_OFF..=CC_POLY_ON => {
+                for note in 0..128 {
+                    self.note_off(note);
+                }
+            },
+            _ => {
+                let sostenuto = self.sostenuto();
+                self.controllers[cc as usize] = val;
+                match cc {
+                    CC_SUSTAIN => self.release(),
+                    CC_SOSTENUTO if !sostenuto && self.sostenuto() => {
+                        for (latched, key) in self.latched.iter_mut().zip(self.keys.iter()) {
+                            *latched = key.is_some();
+                        }
+                    },
+                    CC_SOSTENUTO if !self.sostenuto() => {
+                        self.latched = [false; 128];
+                        self.release();
+                    },
+                    CC_RPN_MSB | CC_RPN_LSB => self.parameter = Some(Parameter::Rpn),
+                    CC_NRPN_MSB | CC_NRPN_LSB => self.parameter = Some(Parameter::Nrpn),
+                    // a new MSB resets the LSB
+                    CC_DATA_ENTRY_MSB => self.update_parameter(|_| (val as U14) << 7),
+                    CC_DATA_ENTRY_LSB => self.update_parameter(|v| (v & !0x7F) | val as U14),
+                    CC_DATA_INCREMENT => self.update_parameter(|v| min(v + 1, RPN_NULL)),
+                    CC_DATA_DECREMENT => self.update_parameter(|v| v.saturating_sub(1)),
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    fn select(&mut self, msb_cc: U7, lsb_cc: U7, parameter: U14) {
+        let (msb, lsb) = u14_to_msb_lsb(parameter);
+        self.control_change(msb_cc, msb);
+        self.control_change(lsb_cc, lsb);
+    }
+
+    fn update_parameter<F>(&mut self, f: F) where F: FnOnce(U14) -> U14 {
+        let c = &self.controllers;
+        // a parameter that has not been set starts from its power-up value
+        let (parameters, number, default) = match self.parameter {
+            Some(Parameter::Rpn) => {
+                let n = msb_lsb_to_u14(c[CC_RPN_MSB as usize], c[CC_RPN_LSB as usize]);
+                (&mut self.rpns, n, default_rpn(n).unwrap_or(0))
+            },
+            Some(Parameter::Nrpn) => {
+                let n = msb_lsb_to_u14(c[CC_NRPN_MSB as usize], c[CC_NRPN_LSB as usize]);
+                (&mut self.nrpns, n, 0)
+            },
+            None => return
+        };
+        if number != RPN_NULL {
+            let value = parameters.entry(number).or_insert(default);
+            *value = f(*value);
+        }
+    }
+
+    fn note_off(&mut self, note: U7) {
+        self.keys[mask7(note) as usize] = None;
+        self.release();
+    }
+
+    // stop any notes that are no longer held by a key or a pedal
+    fn release(&mut self) {
+        let sustain = self.sustain();
+        for n in 0..128 {
+            if self.keys[n].is_none() && !sustain && !self.latched[n] {
+                self.sounding[n] = None;
+            }
+        }
+    }
+
+    // as per the General MIDI recommended practice (RP-015), the bank, volume, balance, pan, sound
+    // and effects controllers and the values of the RPNs and NRPNs are left unchanged
+    fn reset_controllers(&mut self) {
+        for cc in 0..120 {
+            match cc {
+                CC_BANK_SELECT_MSB | CC_VOLUME | CC_BALANCE | CC_PAN | CC_BANK_SELECT_LSB |
+                70..=79 | CC_REVERB..=CC_PHASER => (),
+                _ => self.controllers[cc as usize] = default_controller(cc)
+            }
+        }
+        self.pitch_bend = PITCH_BEND_CENTER;
+        self.channel_pressure = 0;
+        self.poly_pressure = [0; 128];
+        self.parameter = None;
+        self.latched = [false; 128];
+        self.release();
+    }
+}
+
+impl Default for ChannelState {
+    fn default() -> ChannelState {
+        ChannelState::new()
+    }
+}
+
+/// The power-up value of a controller
+fn default_controller(cc: U7) -> U7 {
+    match cc {
+        CC_VOLUME => 100,
+        CC_BALANCE | CC_PAN => 64,
+        CC_EXPRESSION => 127,
+        CC_NRPN_LSB..=CC_RPN_MSB => 127,
+        _ => 0
+    }
+}
+
+/// The power-up value of the RPNs defined by the spec
+fn default_rpn(rpn: U14) -> Option<U14> {
+    match rpn {
+        RPN_PITCH_BEND_SENSITIVITY => Some(2 << 7),
+        RPN_FINE_TUNING | RPN_COARSE_TUNING => Some(8192),
+        _ => None
+    }
+}
+
+// use the 7 bit message if the value has no LSB
+fn parameter_message(short: fn(Channel, U14, U7) -> Message,
+                     long: fn(Channel, U14, U14) -> Message,
+                     channel: Channel,
+                     parameter: U14,
+                     value: U14) -> Message {
+    match u14_to_msb_lsb(value) {
+        (msb, 0) => short(channel, parameter, msb),
+        _ => long(channel, parameter, value)
+    }
+}
+
+/// The state of all 16 Midi channels
+///
+/// Channel messages update the state of their channel, and `SystemReset` returns every channel to
+/// its power-up state.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MidiState {
+    channels: Vec<ChannelState>
+}
+
+impl MidiState {
+    pub fn new() -> MidiState {
+        MidiState { channels: vec![ChannelState::new(); 16] }
+    }
+
+    pub fn process(&mut self, message: &Message) {
+        match message.channel() {
+            Some(ch) => self.channels[ch as usize].process(message),
+            None => {
+                for channel in &mut self.channels {
+                    channel.process(message);
+                }
+            }
+        }
+    }
+
+    pub fn channel(&self, channel: Channel) -> &ChannelState {
+        &self.channels[channel as usize]
+    }
+
+    /// The messages needed to bring a receiver from its power-up state to this state, one channel
+    /// after another (see `ChannelState::chase`)
+    pub fn chase(&self) -> Vec<Message> {
+        self.channels.iter()
+            .enumerate()
+            .flat_map(|(ch, state)| state.chase(FromPrimitive::from_usize(ch).unwrap()))
+            .collect()
+    }
+
+    /// The state of each channel, starting with `Ch1`
+    pub fn channels(&self) -> &[ChannelState] {
+        &self.channels
+    }
+}
+
+impl Default for MidiState {
+    fn default() -> MidiState {
+        MidiState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Channel::*;
+
+    fn state(messages: &[Message]) -> ChannelState {
+        let mut state = ChannelState::new();
+        for message in messages {
+            state.process(message);
+        }
+        state
+    }
+
+    #[test]
+    fn test_controllers() {
+        let s = state(&[ControlChange(Ch1, CC_VOLUME, 90),
+                        ControlChange(Ch1, CC_BANK_SELECT_MSB, 1),
+                        ControlChange(Ch1, CC_BANK_SELECT_LSB, 2),
+                        ProgramChange(Ch1, 5),
+                        ControlChange(Ch1, CC_BANK_SELECT_MSB, 3),
+                        PitchBend(Ch1, 100),
+                        ChannelPressure(Ch1, 20),
+                        PolyphonicPressure(Ch1, 60, 30),
+                        LocalControlOff(Ch1)]);
+        assert_eq!(s.controller(CC_VOLUME), 90);
+        assert_eq!(s.controller(CC_PAN), 64);
+        assert_eq!((s.program(), s.bank()), (5, 130));
+        assert_eq!((s.pitch_bend(), s.channel_pressure(), s.poly_pressure(60)), (100, 20, 30));
+        assert!(!s.local_control());
+    }
+
+    #[test]
+    fn test_rpn() {
+        let s = state(&[RPN7(Ch1, RPN_PITCH_BEND_SENSITIVITY, 12),
+                        NRPN14(Ch1, 1000, 1001),
+                        // raw coarse tuning, with an increment
+                        ControlChange(Ch1, CC_RPN_MSB, 0),
+                        ControlChange(Ch1, CC_RPN_LSB, 2),
+                        ControlChange(Ch1, CC_DATA_ENTRY_MSB, 64),
+                        ControlChange(Ch1, CC_DATA_INCREMENT, 0),
+                        // nothing is selected after the null RPN
+                        RPN7(Ch1, RPN_NULL, 1),
+                        ControlChange(Ch1, CC_DATA_ENTRY_MSB, 10)]);
+        assert_eq!(s.pitch_bend_range(), (12, 0));
+        assert_eq!(s.nrpn(1000), Some(1001));
+        assert_eq!(s.rpns(), vec![(RPN_PITCH_BEND_SENSITIVITY, 12 << 7),
+                                  (RPN_COARSE_TUNING, 8193)]);
+        assert_eq!(ChannelState::new().pitch_bend_range(), (2, 0));
+
+        // only the cents of the bend range are changed
+        let s = state(&[ControlChange(Ch1, CC_RPN_MSB, 0),
+                        ControlChange(Ch1, CC_RPN_LSB, 0),
+                        ControlChange(Ch1, CC_DATA_ENTRY_LSB, 50)]);
+        assert_eq!(s.pitch_bend_range(), (2, 50));
+        let s = state(&[ControlChange(Ch1, CC_RPN_MSB, 0),
+                        ControlChange(Ch1, CC_RPN_LSB, 1),
+                        ControlChange(Ch1, CC_DATA_INCREMENT, 0)]);
+        assert_eq!(s.rpn(RPN_FINE_TUNING), Some(8193));
+    }
+
+    #[test]
+    fn test_notes_and_pedals() {
+        let mut s = state(&[NoteOn(Ch1, 60, 100),
+                            NoteOn(Ch1, 62, 90),
+                            NoteOn(Ch1, 62, 0),
+                            NoteOn(Ch1, 64, 80),
+                            ControlChange(Ch1, CC_SOSTENUTO, 127),
+                            NoteOff(Ch1, 64, 0),
+                            NoteOn(Ch1, 65, 70),
+                            ControlChange(Ch1, CC_SUSTAIN, 127),
+                            NoteOff(Ch1, 65, 0)]);
+        assert_eq!(s.notes(), vec![(60, 100), (64, 80), (65, 70)]);
+        assert!(s.is_key_down(60) && !s.is_key_down(64));
+
+        s.process(&ControlChange(Ch1, CC_SUSTAIN, 0));
+        assert_eq!(s.notes(), vec![(60, 100), (64, 80)]);
+        s.process(&ControlChange(Ch1, CC_SOSTENUTO, 0));
+        assert_eq!(s.notes(), vec![(60, 100)]);
+    }
+
+    #[test]
+    fn test_resets() {
+        let mut s = state(&[NoteOn(Ch1, 60, 100),
+                            ControlChange(Ch1, CC_SUSTAIN, 127),
+                            AllNotesOff(Ch1)]);
+        // held by the sustain pedal
+        assert_eq!(s.notes(), vec![(60, 100)]);
+
+        for message in &[ControlChange(Ch1, CC_VOLUME, 90),
+                         ControlChange(Ch1, CC_MODULATION, 10),
+                         ControlChange(Ch1, CC_EXPRESSION, 10),
+                         PitchBend(Ch1, 0),
+                         RPN7(Ch1, RPN_PITCH_BEND_SENSITIVITY, 12),
+                         ResetAllControllers(Ch1)] {
+            s.process(message);
+        }
+        assert!(s.notes().is_empty());
+        assert_eq!(s.controller(CC_VOLUME), 90);
+        assert_eq!(s.controller(CC_MODULATION), 0);
+        assert_eq!(s.controller(CC_EXPRESSION), 127);
+        assert_eq!(s.pitch_bend(), PITCH_BEND_CENTER);
+        assert_eq!(s.pitch_bend_range(), (12, 0));
+
+        // data entry is ignored until a parameter is selected again
+        s.process(&ControlChange(Ch1, CC_DATA_ENTRY_MSB, 1));
+        assert_eq!(s.pitch_bend_range(), (12, 0));
+
+        s.process(&SystemReset);
+        assert_eq!(s, ChannelState::new());
+    }
+
+    #[test]
+    fn test_midi_state() {
+        let mut s = MidiState::new();
+        s.process(&NoteOn(Ch2, 60, 100));
+        s.process(&ControlChange(Ch3, CC_VOLUME, 1));
+        assert_eq!(s.channel(Ch2).notes(), vec![(60, 100)]);
+        assert!(s.channel(Ch1).notes().is_empty());
+        assert_eq!(s.channel(Ch3).controller(CC_VOLUME), 1);
+        s.process(&SystemReset);
+        assert_eq!(s, MidiState::new());
+    }
+
+    #[test]
+    fn test_chase() {
+        let s = state(&[ControlChange(Ch1, CC_BANK_SELECT_MSB, 1),
+                        ProgramChange(Ch1, 5),
+                        ControlChange(Ch1, CC_VOLUME, 100),
+                        ControlChange(Ch1, CC_PAN, 0),
+                        ControlChange(Ch1, CC_SUSTAIN, 127),
+                        RPN7(Ch1, RPN_PITCH_BEND_SENSITIVITY, 2),
+                        RPN7(Ch1, RPN_COARSE_TUNING, 66),
+                        NRPN14(Ch1, 1000, 1001),
+                        PitchBend(Ch1, 0),
+                        ChannelPressure(Ch1, 10),
+                        NoteOn(Ch1, 60, 100)]);
+        assert_eq!(s.chase(Ch2), vec![ControlChange(Ch2, CC_BANK_SELECT_MSB, 1),
+                                      ControlChange(Ch2, CC_BANK_SELECT_LSB, 0),
+                                      ProgramChange(Ch2, 5),
+                                      ControlChange(Ch2, CC_PAN, 0),
+                                      ControlChange(Ch2, CC_SUSTAIN, 127),
+                                      RPN7(Ch2, RPN_COARSE_TUNING, 66),
+                                      NRPN14(Ch2, 1000, 1001),
+                                      PitchBend(Ch2, 0),
+                                      ChannelPressure(Ch2, 10),
+                                      ControlChange(Ch2, CC_RPN_MSB, 127),
+                                      ControlChange(Ch2, CC_RPN_LSB, 127)]);
+        assert_eq!(ChannelState::new().chase(Ch1), vec![]);
+    }
+
+    #[test]
+    fn test_chase_replay() {
+        let mut s = MidiState::new();
+        for message in &[ProgramChange(Ch1, 5),
+                         ControlChange(Ch1, CC_BANK_SELECT_LSB, 3),
+                         LocalControlOff(Ch2),
+                         PolyphonicPressure(Ch3, 60, 10),
+                         RPN14(Ch4, RPN_FINE_TUNING, 1),
+                         NRPN7(Ch4, 1, 2),
+                         ControlChange(Ch16, CC_EXPRESSION, 0)] {
+            s.process(message);
+        }
+        let chase = s.chase();
+        let mut replayed = MidiState::new();
+        for message in &chase {
+            replayed.process(message);
+        }
+        assert_eq!(replayed.chase(), chase);
+        assert_eq!(chase.len(), 9);
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+
+use constants::{SYSEX, SYSEX_EOX};
+use manufacturer::Manufacturer;
+use message::Message;
+use to_raw_messages::ToRawMessages;
+
+/// Errors from reading a `.syx` file, the first argument is the offset of the problem in the file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SyxError {
+    /// A byte was found outside of an `F0`…`F7` block
+    UnexpectedByte(usize, u8),
+    /// A status byte was found inside an `F0`…`F7` block
+    UnexpectedStatus(usize, u8),
+    /// A block has no manufacturer ID
+    MissingManufacturer(usize),
+    /// The file ended in the middle of a block
+    Unterminated(usize)
+}
+
+impl fmt::Display for SyxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyxError::UnexpectedByte(offset, b) => {
+                write!(f, "unexpected byte {:02X} outside of SysEx at offset {}", b, offset)
+            },
+            SyxError::UnexpectedStatus(offset, b) => {
+                write!(f, "unexpected status byte {:02X} inside SysEx at offset {}", b, offset)
+            },
+            SyxError::MissingManufacturer(offset) => {
+                write!(f, "SysEx at offset {} has no manufacturer ID", offset)
+            },
+            SyxError::Unterminated(offset) => {
+                write!(f, "SysEx at offset {} is not terminated", offset)
+            }
+        }
+    }
+}
+
+impl Error for SyxError {}
+
+/// Read the contents of a `.syx` file into `Message::SysEx`s
+///
+/// The file should contain back to back `F0`…`F7` blocks, nothing else is allowed.
+pub fn read_syx(bytes: &[u8]) -> Result<Vec<Message>, SyxError> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        if bytes[offset] != SYSEX {
+            return Err(SyxError::UnexpectedByte(offset, bytes[offset]));
+        }
+        let start = offset + 1;
+        let len = bytes[start..].iter().position(|b| *b & 0x80 != 0)
+            .ok_or(SyxError::Unterminated(offset))?;
+        let end = start + len;
+        if bytes[end] != SYSEX_EOX {
+            return Err(SyxError::UnexpectedStatus(end, bytes[end]));
+        }
+        let (manufacturer, data) = Manufacturer::from_u7s(&bytes[start..end])
+            .ok_or(SyxError::MissingManufacturer(offset))?;
+        messages.push(Message::SysEx(manufacturer, data.to_vec()));
+        offset = end + 1;
+    }
+    Ok(messages)
+}
+
+/// Write `Message::SysEx`s to the contents of a `.syx` file
+///
+/// Any messages that are not `Message::SysEx` are ignored.
+pub fn write_syx(messages: &[Message]) -> Vec<u8> {
+    messages.iter()
+        .filter(|m| matches!(**m, Message::SysEx(..)))
+        .flat_map(|m| m.to_raw_messages())
+        .flat_map(|r| r.to_bytes())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manufacturer::Manufacturer::*;
+    use message::Message::*;
+    use types::Channel::*;
+
+    #[test]
+    fn test_read_syx() {
+        let bytes = [0xF0, 0x41, 0x10, 0x42, 0xF7,
+                     0xF0, 0x00, 0x20, 0x33, 0x01, 0xF7,
+                     0xF0, 0x7E, 0xF7];
+        assert_eq!(read_syx(&bytes), Ok(vec![SysEx(OneByte(0x41), vec![0x10, 0x42]),
+                                             SysEx(ThreeByte(0x00, 0x20, 0x33), vec![0x01]),
+                                             SysEx(OneByte(0x7E), vec![])]));
+        assert_eq!(read_syx(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_read_syx_errors() {
+        assert_eq!(read_syx(&[0xF0, 0x41, 0xF7, 0x10]), Err(SyxError::UnexpectedByte(3, 0x10)));
+        assert_eq!(read_syx(&[0xF0, 0x41, 0x90, 0xF7]), Err(SyxError::UnexpectedStatus(2, 0x90)));
+        assert_eq!(read_syx(&[0xF0, 0x41, 0xF7, 0xF0, 0x41, 0x01]),
+                   Err(SyxError::Unterminated(3)));
+        assert_eq!(read_syx(&[0xF0, 0xF7]), Err(SyxError::MissingManufacturer(0)));
+        assert_eq!(read_syx(&[0xF0, 0x00, 0x20, 0xF7]), Err(SyxError::MissingManufacturer(0)));
+    }
+
+    #[test]
+    fn test_write_syx() {
+        let messages = vec![SysEx(OneByte(0x41), vec![0x10, 0x42]),
+                            NoteOn(Ch1, 60, 100),
+                            SysEx(ThreeByte(0x00, 0x20, 0x33), vec![0x01])];
+        let bytes = write_syx(&messages);
+        assert_eq!(bytes, vec![0xF0, 0x41, 0x10, 0x42, 0xF7,
+                               0xF0, 0x00, 0x20, 0x33, 0x01, 0xF7]);
+        assert_eq!(read_syx(&bytes).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,527 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::time::Duration;
+
+use types::U7;
+use message::Message;
+use Message::{MtcQuarterFrame, SysEx};
+use smf::MetaEvent;
+use universal::{UniversalSysEx, RealTime};
+use utils::mask7;
+
+/// The sub-ID#2 of the MTC Full Frame message
+const FULL_FRAME: U7 = 0x01;
+
+/// Frames in 10 minutes of 29.97 drop-frame, 2 frames are dropped in 9 of every 10 minutes
+const DROP_FRAME_10_MINUTES: u32 = 10 * 60 * 30 - 9 * 2;
+
+/// A SMPTE frame rate, as used by Midi Time Code
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FrameRate {
+    #[cfg_attr(feature = "serde", serde(rename = "24"))]
+    Fps24,
+    #[cfg_attr(feature = "serde", serde(rename = "25"))]
+    Fps25,
+    /// 29.97 drop-frame, frame labels 0 and 1 are skipped at the start of each minute, except
+    /// every tenth minute
+    #[cfg_attr(feature = "serde", serde(rename = "29.97df"))]
+    Fps29_97Df,
+    #[cfg_attr(feature = "serde", serde(rename = "30"))]
+    Fps30
+}
+
+impl FrameRate {
+    /// The rate as sent in Midi Time Code, 0-3
+    pub fn code(self) -> U7 {
+        match self {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps29_97Df => 2,
+            FrameRate::Fps30 => 3
+        }
+    }
+
+    pub fn from_code(code: U7) -> Option<FrameRate> {
+        match code {
+            0 => Some(FrameRate::Fps24),
+            1 => Some(FrameRate::Fps25),
+            2 => Some(FrameRate::Fps29_97Df),
+            3 => Some(FrameRate::Fps30),
+            _ => None
+        }
+    }
+
+    /// The number of frame labels per second, 30 for 29.97 drop-frame
+    pub fn nominal_fps(self) -> u32 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps29_97Df | FrameRate::Fps30 => 30
+        }
+    }
+
+    /// Frames per second, as `(numerator, denominator)`
+    fn fraction(self) -> (u64, u64) {
+        match self {
+            FrameRate::Fps29_97Df => (30000, 1001),
+            _ => (self.nominal_fps() as u64, 1)
+        }
+    }
+
+    /// The number of frames in 24 hours
+    pub fn frames_per_day(self) -> u32 {
+        match self {
+            FrameRate::Fps29_97Df => DROP_FRAME_10_MINUTES * 6 * 24,
+            _ => self.nominal_fps() * 60 * 60 * 24
+        }
+    }
+}
+
+/// A SMPTE time, `hours:minutes:seconds:frames` at a frame rate
+///
+/// Arithmetic wraps around at 24 hours, and takes the skipped frame labels of drop-frame into
+/// account.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub rate: FrameRate
+}
+
+impl Timecode {
+    /// Returns `None` if any field is out of range, or the frame is skipped by drop-frame
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate)
+               -> Option<Timecode> {
+        let dropped = rate == FrameRate::Fps29_97Df && seconds == 0 && frames < 2 &&
+            !minutes.is_multiple_of(10);
+        if hours < 24 && minutes < 60 && seconds < 60 && (frames as u32) < rate.nominal_fps() &&
+            !dropped {
+            Some(Timecode { hours, minutes, seconds, frames, rate })
+        }
+        else {
+            None
+        }
+    }
+
+    /// The number of frames since `00:00:00:00`
+    pub fn to_frames(&self) -> u32 {
+        let fps = self.rate.nominal_fps();
+        let minutes = self.hours as u32 * 60 + self.minutes as u32;
+        let frames = (minutes * 60 + self.seconds as u32) * fps + self.frames as u32;
+        match self.rate {
+            FrameRate::Fps29_97Df => frames - 2 * (minutes - minutes / 10),
+            _ => frames
+        }
+    }
+
+    /// The time `frames` frames after `00:00:00:00`, wrapping around at 24 hours
+    pub fn from_frames(frames: u32, rate: FrameRate) -> Timecode {
+        let mut frames = frames % rate.frames_per_day();
+        if rate == FrameRate::Fps29_97Df {
+            // add back the skipped frame labels
+            let (tens, rest) = (frames / DROP_FRAME_10_MINUTES, frames % DROP_FRAME_10_MINUTES);
+            frames += 18 * tens + if rest < 2 { 0 } else { 2 * ((rest - 2) / 1798) };
+        }
+        let fps = rate.nominal_fps();
+        let seconds = frames / fps;
+        Timecode {
+            hours: (seconds / 3600) as u8,
+            minutes: (seconds / 60 % 60) as u8,
+            seconds: (seconds % 60) as u8,
+            frames: (frames % fps) as u8,
+            rate
+        }
+    }
+
+    /// The time `frames` later (or earlier if negative), wrapping around at 24 hours
+    pub fn add_frames(&self, frames: i64) -> Timecode {
+        let day = self.rate.frames_per_day() as i64;
+        let frames = (self.to_frames() as i64 + frames).rem_euclid(day);
+        Timecode::from_frames(frames as u32, self.rate)
+    }
+
+    /// The 8 `MtcQuarterFrame` messages that send this time, in order
+    pub fn quarter_frames(&self) -> Vec<Message> {
+        let hours = self.hours_with_rate();
+        let values = [self.frames & 0xF, self.frames >> 4,
+                      self.seconds & 0xF, self.seconds >> 4,
+                      self.minutes & 0xF, self.minutes >> 4,
+                      hours & 0xF, hours >> 4];
+        values.iter().enumerate().map(|(piece, v)| MtcQuarterFrame(piece as U7, *v)).collect()
+    }
+
+    /// The MTC Full Frame message, a Universal Real Time SysEx, used to locate a receiver to this
+    /// time
+    pub fn to_full_frame(&self, device: U7) -> Message {
+        let data = vec![FULL_FRAME,
+                        self.hours_with_rate(), self.minutes, self.seconds, self.frames];
+        UniversalSysEx::RealTime(device, RealTime::Mtc, data).to_message()
+    }
+
+    /// Read an MTC Full Frame message for any device
+    pub fn from_full_frame(message: &Message) -> Option<Timecode> {
+        match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::RealTime(_, RealTime::Mtc, data) => match data[..] {
+                [FULL_FRAME, hr, mn, se, fr] => Timecode::from_hours_with_rate(hr, mn, se, fr),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// The Standard Midi File meta event for this time, with `subframes` in 1/100ths of a frame
+    pub fn to_smpte_offset(&self, subframes: u8) -> MetaEvent {
+        MetaEvent::SmpteOffset(self.hours_with_rate(), self.minutes, self.seconds, self.frames,
+                               subframes)
+    }
+
+    /// Read a Standard Midi File SMPTE offset meta event, as the time and the subframes
+    pub fn from_smpte_offset(meta: &MetaEvent) -> Option<(Timecode, u8)> {
+        match *meta {
+            MetaEvent::SmpteOffset(hr, mn, se, fr, ff) => {
+                Timecode::from_hours_with_rate(hr, mn, se, fr).map(|t| (t, ff))
+            },
+            _ => None
+        }
+    }
+
+    // hours with the frame rate in bits 5 and 6, as used by full frames and SMF
+    fn hours_with_rate(&self) -> u8 {
+        self.rate.code() << 5 | self.hours
+    }
+
+    fn from_hours_with_rate(hr: u8, mn: u8, se: u8, fr: u8) -> Option<Timecode> {
+        let rate = FrameRate::from_code((hr >> 5) & 0b11)?;
+        Timecode::new(hr & 0b11111, mn, se, fr, rate)
+    }
+}
+
+// the 5 byte time code used by MMC and MSC, hours with the frame rate in bits 5 and 6, then
+// minutes, seconds, frames and subframes, the other bits of the minutes, seconds and frames are
+// flags
+pub(crate) fn time_code_bytes(time: &Timecode, subframes: U7) -> [U7; 5] {
+    [time.hours_with_rate(), time.minutes, time.seconds, time.frames, mask7(subframes)]
+}
+
+pub(crate) fn time_code_from_bytes(bytes: &[U7]) -> Option<(Timecode, U7)> {
+    match *bytes {
+        [hr, mn, sc, fr, st] => {
+            let rate = FrameRate::from_code((hr >> 5) & 0b11)?;
+            Timecode::new(hr & 0x1F, mn & 0x3F, sc & 0x3F, fr & 0x1F, rate).map(|t| (t, st))
+        },
+        _ => None
+    }
+}
+
+/// Formats as `hh:mm:ss:ff`, or `hh:mm:ss;ff` for drop-frame
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = if self.rate == FrameRate::Fps29_97Df { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{}{:02}",
+               self.hours, self.minutes, self.seconds, separator, self.frames)
+    }
+}
+
+/// The direction that Midi Time Code is running in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    Forward,
+    Reverse
+}
+
+/// Assembles `MtcQuarterFrame` messages into a `Timecode`
+///
+/// A full time is sent as 8 quarter frames over 2 frames, pieces 0 to 7 when running forwards and
+/// 7 to 0 in reverse. The time sent is the time when the first piece was sent, so the decoder adds
+/// (or in reverse subtracts) the 2 frames that have passed, and then counts a frame every 4
+/// quarter frames until the next time is complete.
+///
+/// A piece that doesn't follow on from the last is counted as a dropout, and nothing is returned
+/// until 8 pieces have arrived in order again. An MTC Full Frame message sets the time directly.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MtcDecoder {
+    pieces: [U7; 8],
+    // how many pieces in a row have been received in order
+    count: usize,
+    last: Option<U7>,
+    direction: Option<Direction>,
+    time: Option<Timecode>,
+    dropouts: u32
+}
+
+impl MtcDecoder {
+    pub fn new() -> MtcDecoder {
+        MtcDecoder::default()
+    }
+
+    /// Process a message, returns the current time if it has changed
+    pub fn process(&mut self, message: &Message) -> Option<Timecode> {
+        match *message {
+            MtcQuarterFrame(piece, value) => self.quarter_frame(piece & 0b111, value & 0b1111),
+            SysEx(..) => {
+                let time = Timecode::from_full_frame(message)?;
+                self.count = 0;
+                self.last = None;
+                self.time = Some(time);
+                self.time
+            },
+            _ => None
+        }
+    }
+
+    /// The current time, `None` until a full time has been received
+    pub fn time(&self) -> Option<Timecode> {
+        self.time
+    }
+
+    /// The direction the time code is running, `None` until 2 pieces have been received in order
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// The number of times the sequence of quarter frames has been broken
+    pub fn dropouts(&self) -> u32 {
+        self.dropouts
+    }
+
+    fn quarter_frame(&mut self, piece: U7, value: U7) -> Option<Timecode> {
+        let direction = self.last.and_then(|last| {
+            if piece == (last + 1) % 8 {
+                Some(Direction::Forward)
+            }
+            else if (piece + 1) % 8 == last {
+                Some(Direction::Reverse)
+            }
+            else {
+                None
+            }
+        });
+        self.last = Some(piece);
+        self.pieces[piece as usize] = value;
+
+        match direction {
+            Some(d) if self.direction.is_none() || self.direction == Some(d) => self.count += 1,
+            _ => {
+                if self.count > 0 {
+                    self.dropouts += 1;
+                }
+                self.count = 1;
+                self.time = None;
+            }
+        }
+        self.direction = direction.or(self.direction);
+
+        // the last piece completes a time, and the middle piece is a frame later
+        let (last, middle, frames) = match self.direction {
+            Some(Direction::Forward) => (7, 3, 1),
+            Some(Direction::Reverse) => (0, 4, -1),
+            None => return None
+        };
+        if piece == last && self.count >= 8 {
+            self.time = self.assemble().map(|time| time.add_frames(2 * frames));
+            self.time
+        }
+        else if piece == middle {
+            self.time = self.time.map(|time| time.add_frames(frames));
+            self.time
+        }
+        else {
+            None
+        }
+    }
+
+    fn assemble(&self) -> Option<Timecode> {
+        let p = &self.pieces;
+        let rate = FrameRate::from_code((p[7] >> 1) & 0b11)?;
+        Timecode::new((p[7] & 1) << 4 | p[6],
+                      p[5] << 4 | p[4],
+                      p[3] << 4 | p[2],
+                      p[1] << 4 | p[0],
+                      rate)
+    }
+}
+
+/// Generates Midi Time Code quarter frames, 4 per frame
+///
+/// The time of each quarter frame is calculated from the start rather than by adding up
+/// intervals, so the time code does not drift. Times are given by the caller, as the time since
+/// any fixed point.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MtcGenerator {
+    start: Timecode,
+    origin: Duration,
+    next: u64
+}
+
+impl MtcGenerator {
+    /// A generator that sends `start` from `origin`
+    pub fn new(start: Timecode, origin: Duration) -> MtcGenerator {
+        MtcGenerator { start, origin, next: 0 }
+    }
+
+    /// Jump to `time` at `origin`, returns the MTC Full Frame message that should be sent to
+    /// `device` (0x7F for all devices)
+    pub fn locate(&mut self, time: Timecode, origin: Duration, device: U7) -> Message {
+        *self = MtcGenerator::new(time, origin);
+        time.to_full_frame(device)
+    }
+
+    /// The time of the frame being sent
+    pub fn time(&self) -> Timecode {
+        self.start.add_frames((self.next / 4) as i64)
+    }
+
+    /// The time that the next quarter frame is due
+    pub fn next_time(&self) -> Duration {
+        let (num, den) = self.start.rate.fraction();
+        let nanos = self.next as u128 * 1_000_000_000 * den as u128 / (4 * num as u128);
+        self.origin + Duration::from_nanos(nanos as u64)
+    }
+
+    /// Returns the quarter frames that are due by `now`, with the time each one is scheduled for
+    pub fn poll(&mut self, now: Duration) -> Vec<(Duration, Message)> {
+        let mut messages = Vec::new();
+        loop {
+            let time = self.next_time();
+            if time > now {
+                return messages;
+            }
+            // each group of 8 pieces sends the time of the frame it started on
+            let piece = (self.next % 8) as usize;
+            let frame = self.start.add_frames(((self.next - piece as u64) / 4) as i64);
+            messages.push((time, frame.quarter_frames().swap_remove(piece)));
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::FrameRate::*;
+    use manufacturer::Manufacturer;
+
+    fn tc(h: u8, m: u8, s: u8, f: u8, rate: FrameRate) -> Timecode {
+        Timecode::new(h, m, s, f, rate).unwrap()
+    }
+
+    fn pieces(time: Timecode) -> Vec<Message> {
+        time.quarter_frames()
+    }
+
+    #[test]
+    fn test_drop_frame() {
+        assert_eq!(Timecode::new(0, 1, 0, 0, Fps29_97Df), None);
+        assert_eq!(Timecode::new(0, 10, 0, 0, Fps29_97Df), Some(tc(0, 10, 0, 0, Fps29_97Df)));
+        assert_eq!(Timecode::new(0, 0, 0, 25, Fps25), None);
+
+        assert_eq!(tc(0, 0, 59, 29, Fps29_97Df).add_frames(1), tc(0, 1, 0, 2, Fps29_97Df));
+        assert_eq!(tc(0, 1, 0, 2, Fps29_97Df).add_frames(-1), tc(0, 0, 59, 29, Fps29_97Df));
+        assert_eq!(tc(0, 9, 59, 29, Fps29_97Df).add_frames(1), tc(0, 10, 0, 0, Fps29_97Df));
+        assert_eq!(tc(0, 10, 0, 0, Fps29_97Df).to_frames(), 17982);
+        assert_eq!(tc(23, 59, 59, 29, Fps29_97Df).to_frames() + 1, Fps29_97Df.frames_per_day());
+        assert_eq!(tc(23, 59, 59, 23, Fps24).add_frames(1), tc(0, 0, 0, 0, Fps24));
+        assert_eq!(tc(0, 0, 0, 0, Fps25).add_frames(-1), tc(23, 59, 59, 24, Fps25));
+
+        for frames in (0..Fps29_97Df.frames_per_day()).step_by(7) {
+            assert_eq!(Timecode::from_frames(frames, Fps29_97Df).to_frames(), frames);
+        }
+    }
+
+    #[test]
+    fn test_forward() {
+        let mut d = MtcDecoder::new();
+        let mut updates = Vec::new();
+        for message in pieces(tc(1, 2, 3, 4, Fps25)).iter().chain(&pieces(tc(1, 2, 3, 6, Fps25))) {
+            updates.extend(d.process(message));
+        }
+        assert_eq!(updates, vec![tc(1, 2, 3, 6, Fps25),
+                                 tc(1, 2, 3, 7, Fps25),
+                                 tc(1, 2, 3, 8, Fps25)]);
+        assert_eq!(d.direction(), Some(Direction::Forward));
+        assert_eq!(d.dropouts(), 0);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut d = MtcDecoder::new();
+        let mut updates = Vec::new();
+        let mut messages = pieces(tc(0, 0, 1, 0, Fps30));
+        messages.reverse();
+        for message in &messages {
+            updates.extend(d.process(message));
+        }
+        assert_eq!(updates, vec![tc(0, 0, 0, 28, Fps30)]);
+        assert_eq!(d.direction(), Some(Direction::Reverse));
+    }
+
+    #[test]
+    fn test_dropout() {
+        let mut d = MtcDecoder::new();
+        let mut messages = pieces(tc(0, 0, 0, 0, Fps24));
+        messages.remove(5);
+        for message in &messages {
+            assert_eq!(d.process(message), None);
+        }
+        assert_eq!((d.time(), d.dropouts()), (None, 1));
+
+        for message in &pieces(tc(0, 0, 0, 2, Fps24)) {
+            d.process(message);
+        }
+        assert_eq!(d.time(), Some(tc(0, 0, 0, 4, Fps24)));
+    }
+
+    #[test]
+    fn test_full_frame() {
+        let time = tc(1, 2, 3, 4, Fps29_97Df);
+        let message = time.to_full_frame(0x7F);
+        assert_eq!(message, SysEx(Manufacturer::OneByte(0x7F),
+                                  vec![0x7F, 0x01, 0x01, 0x41, 2, 3, 4]));
+        assert_eq!(Timecode::from_full_frame(&message), Some(time));
+        assert_eq!(Timecode::from_full_frame(&SysEx(Manufacturer::OneByte(0x7F), vec![])), None);
+
+        let mut d = MtcDecoder::new();
+        assert_eq!(d.process(&message), Some(time));
+
+        let meta = time.to_smpte_offset(50);
+        assert_eq!(meta, MetaEvent::SmpteOffset(0x41, 2, 3, 4, 50));
+        assert_eq!(Timecode::from_smpte_offset(&meta), Some((time, 50)));
+        assert_eq!(format!("{}", time), "01:02:03;04");
+        assert_eq!(format!("{}", tc(1, 2, 3, 4, Fps25)), "01:02:03:04");
+    }
+
+    #[test]
+    fn test_generator() {
+        let origin = Duration::from_secs(10);
+        let mut g = MtcGenerator::new(tc(0, 0, 59, 28, Fps29_97Df), origin);
+        let mut d = MtcDecoder::new();
+        let messages = g.poll(origin + Duration::from_millis(300));
+        // 4 quarter frames per frame at 29.97 is one every 8341667ns
+        assert_eq!(messages.len(), 36);
+        assert_eq!(messages[1].0, origin + Duration::from_nanos(8_341_666));
+        for (_, message) in &messages {
+            d.process(message);
+        }
+        assert_eq!(d.time(), Some(tc(0, 1, 0, 9, Fps29_97Df)));
+        assert_eq!(g.time(), d.time().unwrap());
+
+        // an hour of 29.97 is 3603.6 seconds
+        let mut g = MtcGenerator::new(tc(0, 0, 0, 0, Fps29_97Df), origin);
+        g.next = 4 * 108_000;
+        assert_eq!(g.next_time(), origin + Duration::from_millis(3_603_600));
+        // which is 108 frames more than an hour of drop-frame labels
+        assert_eq!(g.time(), tc(1, 0, 3, 18, Fps29_97Df));
+
+        let locate = g.locate(tc(2, 0, 0, 0, Fps29_97Df), origin, 0x10);
+        assert_eq!(Timecode::from_full_frame(&locate), Some(tc(2, 0, 0, 0, Fps29_97Df)));
+        assert_eq!(g.poll(origin).len(), 1);
+    }
+}
@@ -28,31 +28,40 @@ impl ToRawMessages for RawMessage {
 
 impl ToRawMessages for Message {
     fn to_raw_messages(&self) -> Vec<RawMessage> {
-        match self {
+        match *self {
+            // System common
+            MtcQuarterFrame(piece, value) => {
+                vec!(StatusData(MTC_QUARTER_FRAME, (piece & 0b111) << 4 | (value & 0b1111)))
+            },
+            SongPositionPointer(position) => {
+                let (msb, lsb) = u14_to_msb_lsb(position);
+                vec!(StatusDataData(SONG_POSITION_POINTER, lsb, msb))
+            },
+
             // System realtime
-            &Start => vec!(Status(START)),
-            &TimingClock => vec!(Status(TIMING_CLOCK)),
-            &Continue => vec!(Status(CONTINUE)),
-            &Stop => vec!(Status(STOP)),
-            &ActiveSensing => vec!(Status(ACTIVE_SENSING)),
-            &SystemReset => vec!(Status(SYSTEM_RESET)),
+            Start => vec!(Status(START)),
+            TimingClock => vec!(Status(TIMING_CLOCK)),
+            Continue => vec!(Status(CONTINUE)),
+            Stop => vec!(Status(STOP)),
+            ActiveSensing => vec!(Status(ACTIVE_SENSING)),
+            SystemReset => vec!(Status(SYSTEM_RESET)),
 
             // Channel mode
-            &AllSoundOff(ch) => ControlChange(ch, 120, 0).to_raw_messages(),
-            &ResetAllControllers(ch) => ControlChange(ch, 121, 0).to_raw_messages(),
-            &LocalControlOff(ch) => ControlChange(ch, 122, 0).to_raw_messages(),
-            &LocalControlOn(ch) => ControlChange(ch, 122, 127).to_raw_messages(),
-            &AllNotesOff(ch) => ControlChange(ch, 123, 0).to_raw_messages(),
+            AllSoundOff(ch) => ControlChange(ch, 120, 0).to_raw_messages(),
+            ResetAllControllers(ch) => ControlChange(ch, 121, 0).to_raw_messages(),
+            LocalControlOff(ch) => ControlChange(ch, 122, 0).to_raw_messages(),
+            LocalControlOn(ch) => ControlChange(ch, 122, 127).to_raw_messages(),
+            AllNotesOff(ch) => ControlChange(ch, 123, 0).to_raw_messages(),
 
             // Channel voice
-            &ProgramChange(ch, no) => {
+            ProgramChange(ch, no) => {
                 let sb = status_byte(PROGRAM_CHANGE, ch);
                 vec!(StatusData(sb, mask7(no)))
             },
-            &ControlChange(ch, no, val) => {
+            ControlChange(ch, no, val) => {
                 vec!(cc(ch, mask7(no), mask7(val)))
             },
-            &RPN7(ch, rpn, val) => {
+            RPN7(ch, rpn, val) => {
                 let (rpn_msb, rpn_lsb) = u14_to_msb_lsb(rpn);
                 vec!(
                     cc(ch, CC_RPN_MSB, rpn_msb),
@@ -60,7 +69,7 @@ impl ToRawMessages for Message {
                     cc(ch, CC_DATA_ENTRY_MSB, mask7(val))
                 )
             },
-            &RPN14(ch, rpn, val) => {
+            RPN14(ch, rpn, val) => {
                 let (rpn_msb, rpn_lsb) = u14_to_msb_lsb(rpn);
                 let (val_msb, val_lsb) = u14_to_msb_lsb(val);
                 vec!(
@@ -70,7 +79,7 @@ impl ToRawMessages for Message {
                     cc(ch, CC_DATA_ENTRY_LSB, val_lsb)
                 )
             },
-            &NRPN7(ch, nrpn, val) => {
+            NRPN7(ch, nrpn, val) => {
                 let (nrpn_msb, nrpn_lsb) = u14_to_msb_lsb(nrpn);
                 vec!(
                     cc(ch, CC_NRPN_MSB, nrpn_msb),
@@ -78,7 +87,7 @@ impl ToRawMessages for Message {
                     cc(ch, CC_DATA_ENTRY_MSB, mask7(val))
                 )
             },
-            &NRPN14(ch, nrpn, val) => {
+            NRPN14(ch, nrpn, val) => {
                 let (nrpn_msb, nrpn_lsb) = u14_to_msb_lsb(nrpn);
                 let (val_msb, val_lsb) = u14_to_msb_lsb(val);
                 vec!(
@@ -88,32 +97,32 @@ impl ToRawMessages for Message {
                     cc(ch, CC_DATA_ENTRY_LSB, val_lsb)
                 )
             },
-            &SysEx(manufacturer, ref data) => {
+            SysEx(manufacturer, ref data) => {
                 let mut output = Vec::new();
                 output.push(SYSEX);
                 output.extend(manufacturer.to_u7s());
                 output.extend(data.iter().map(|d| mask7(*d)));
                 output.push(SYSEX_EOX);
-                output.into_iter().map(|d| Raw(d)).collect()
+                output.into_iter().map(Raw).collect()
             },
-            &NoteOff(ch, no, vel) => {
+            NoteOff(ch, no, vel) => {
                 let sb = status_byte(NOTE_OFF, ch);
                 vec!(StatusDataData(sb, mask7(no), mask7(vel)))
             },
-            &NoteOn(ch, no, vel) => {
+            NoteOn(ch, no, vel) => {
                 let sb = status_byte(NOTE_ON, ch);
                 vec!(StatusDataData(sb, mask7(no), mask7(vel)))
             },
-            &PitchBend(ch, bend) => {
+            PitchBend(ch, bend) => {
                 let sb = status_byte(PITCH_BEND, ch);
                 let (msb, lsb) = u14_to_msb_lsb(bend);
                 vec!(StatusDataData(sb, lsb, msb))
             }
-            &PolyphonicPressure(ch, no, vel) => {
+            PolyphonicPressure(ch, no, vel) => {
                 let sb = status_byte(POLYPHONIC_PRESSURE, ch);
                 vec!(StatusDataData(sb, mask7(no), mask7(vel)))
             },
-            &ChannelPressure(ch, vel) => {
+            ChannelPressure(ch, vel) => {
                 let sb = status_byte(CHANNEL_PRESSURE, ch);
                 vec!(StatusData(sb, mask7(vel)))
             }
@@ -158,6 +167,13 @@ mod test {
         // SystemReset
         assert_eq!(SystemReset.to_raw_messages(), vec![Status(0b11111111)]);
 
+        // MtcQuarterFrame
+        assert_eq!(MtcQuarterFrame(7, 3).to_raw_messages(), vec![StatusData(0b11110001, 0x73)]);
+
+        // SongPositionPointer
+        assert_eq!(SongPositionPointer(1000).to_raw_messages(),
+                   vec![StatusDataData(0b11110010, 104, 7)]);
+
         // AllSoundOff
         assert_eq!(AllSoundOff(Ch1).to_raw_messages(), vec![StatusDataData(176, 120, 0)]);
 
@@ -0,0 +1,259 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::{Channel, U7};
+use message::Message;
+use Message::{NoteOn, NoteOff};
+
+/// A `Message` positioned at an absolute time in ticks
+///
+/// Events are ordered by `tick` first, and then by the ordering of `Message`, thus events at the
+/// same tick will sort into a sensible order for sending.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Event {
+    pub tick: u32,
+    pub message: Message
+}
+
+impl Event {
+    pub fn new(tick: u32, message: Message) -> Event {
+        Event { tick, message }
+    }
+}
+
+/// An editable, time-sorted list of `Event`s
+///
+/// Events are always kept sorted, any operation that changes the time of an event will move it to
+/// the correct position. Use `from_deltas` and `to_deltas` to convert to and from the delta time
+/// representation used when streaming or storing Midi data.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Track {
+    events: Vec<Event>
+}
+
+impl Track {
+    pub fn new() -> Track {
+        Track { events: Vec::new() }
+    }
+
+    /// Create a `Track` from events in any order
+    pub fn from_events(mut events: Vec<Event>) -> Track {
+        events.sort();
+        Track { events }
+    }
+
+    /// Create a `Track` from `(delta, message)` pairs, where each delta is the number of ticks
+    /// since the previous message, returns `None` if a tick would be past `u32::MAX`
+    pub fn from_deltas<I>(deltas: I) -> Option<Track>
+        where I: IntoIterator<Item = (u32, Message)> {
+        let mut tick = 0u32;
+        let events = deltas.into_iter().map(|(delta, message)| {
+            tick = tick.checked_add(delta)?;
+            Some(Event::new(tick, message))
+        }).collect::<Option<_>>()?;
+        Some(Track::from_events(events))
+    }
+
+    /// Convert to `(delta, message)` pairs, the first delta is relative to tick 0
+    pub fn to_deltas(&self) -> Vec<(u32, Message)> {
+        let mut last = 0;
+        self.events.iter().map(|e| {
+            let delta = e.tick - last;
+            last = e.tick;
+            (delta, e.message.clone())
+        }).collect()
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn into_events(self) -> Vec<Event> {
+        self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The tick of the last event, or 0 if the track is empty
+    pub fn end_tick(&self) -> u32 {
+        self.events.last().map_or(0, |e| e.tick)
+    }
+
+    /// Insert a message at `tick`, returns the index it was inserted at
+    ///
+    /// If an identical event already exists, the new one is inserted after it.
+    pub fn insert(&mut self, tick: u32, message: Message) -> usize {
+        let event = Event::new(tick, message);
+        let index = self.events.partition_point(|e| *e <= event);
+        self.events.insert(index, event);
+        index
+    }
+
+    /// Remove and return the event at `index`
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Event {
+        self.events.remove(index)
+    }
+
+    /// Remove all the events with a tick in the range `start..end`
+    pub fn remove_range(&mut self, start: u32, end: u32) -> Vec<Event> {
+        let (removed, kept) = self.events.drain(..).partition(|e| e.tick >= start && e.tick < end);
+        self.events = kept;
+        removed
+    }
+
+    /// Keep only the events for which `f` returns `true`
+    pub fn retain<F>(&mut self, f: F) where F: FnMut(&Event) -> bool {
+        self.events.retain(f)
+    }
+
+    /// Move the event at `index` to `tick`, returns its new index
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn move_event(&mut self, index: usize, tick: u32) -> usize {
+        let event = self.events.remove(index);
+        self.insert(tick, event.message)
+    }
+
+    /// Copy the events with a tick in the range `start..end` to a new `Track`
+    ///
+    /// The ticks of the new track are relative to `start`, so that the slice can be used as a
+    /// clip.
+    pub fn slice(&self, start: u32, end: u32) -> Track {
+        let events = self.events.iter()
+            .filter(|e| e.tick >= start && e.tick < end)
+            .map(|e| Event::new(e.tick - start, e.message.clone()))
+            .collect();
+        Track { events }
+    }
+
+    /// Move every event to the nearest multiple of `grid` ticks, halfway values round up
+    ///
+    /// A note that would be rounded to a length of 0 is kept `grid` ticks long, so that its
+    /// `NoteOff` still comes after its `NoteOn`. Panics if `grid` is 0.
+    pub fn quantize(&mut self, grid: u32) {
+        assert!(grid > 0, "quantize grid must be greater than 0");
+        let grid = grid as u64;
+        // the quantized tick of each held note, the earliest is ended first
+        let mut held: Vec<(Channel, U7, u64)> = Vec::new();
+        for e in &mut self.events {
+            let mut tick = (e.tick as u64 + grid / 2) / grid * grid;
+            // round down instead if rounding up would overflow
+            if tick > u32::MAX as u64 {
+                tick -= grid;
+            }
+            match e.message {
+                NoteOn(ch, note, vel) if vel > 0 => held.push((ch, note, tick)),
+                NoteOn(ch, note, _) | NoteOff(ch, note, _) => {
+                    if let Some(i) = held.iter().position(|h| h.0 == ch && h.1 == note) {
+                        let (_, _, on) = held.remove(i);
+                        if tick <= on {
+                            tick = (on + grid).min(u32::MAX as u64);
+                        }
+                    }
+                },
+                _ => ()
+            }
+            e.tick = tick as u32;
+        }
+        self.events.sort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::Message::*;
+    use types::Channel::*;
+
+    #[test]
+    fn test_deltas() {
+        let deltas = vec![(0, NoteOn(Ch1, 60, 100)),
+                          (96, NoteOff(Ch1, 60, 0)),
+                          (0, NoteOn(Ch1, 62, 100)),
+                          (96, NoteOff(Ch1, 62, 0))];
+        let track = Track::from_deltas(deltas.clone()).unwrap();
+        assert_eq!(track.end_tick(), 192);
+        assert_eq!(track.events()[2], Event::new(96, NoteOn(Ch1, 62, 100)));
+        assert_eq!(track.to_deltas(), deltas);
+        assert_eq!(Track::from_deltas(vec![(u32::MAX, Start), (1, Stop)]), None);
+    }
+
+    #[test]
+    fn test_sorted() {
+        // NoteOff sorts before NoteOn at the same tick
+        let track = Track::from_deltas(vec![(10, NoteOn(Ch1, 60, 100)),
+                                            (0, NoteOff(Ch1, 60, 0))]).unwrap();
+        assert_eq!(track.to_deltas(), vec![(10, NoteOff(Ch1, 60, 0)),
+                                           (0, NoteOn(Ch1, 60, 100))]);
+    }
+
+    #[test]
+    fn test_insert_remove_move() {
+        let mut track = Track::new();
+        assert_eq!(track.insert(100, NoteOn(Ch1, 60, 100)), 0);
+        assert_eq!(track.insert(0, ProgramChange(Ch1, 5)), 0);
+        assert_eq!(track.insert(100, ControlChange(Ch1, 7, 90)), 1);
+        assert_eq!(track.insert(200, NoteOff(Ch1, 60, 0)), 3);
+        assert_eq!(track.len(), 4);
+
+        assert_eq!(track.move_event(1, 50), 1);
+        assert_eq!(track.events()[1], Event::new(50, ControlChange(Ch1, 7, 90)));
+
+        assert_eq!(track.move_event(3, 10), 1);
+        assert_eq!(track.events()[1], Event::new(10, NoteOff(Ch1, 60, 0)));
+
+        assert_eq!(track.remove(0), Event::new(0, ProgramChange(Ch1, 5)));
+        assert_eq!(track.remove_range(0, 60).len(), 2);
+        assert_eq!(track.events(), &[Event::new(100, NoteOn(Ch1, 60, 100))]);
+    }
+
+    #[test]
+    fn test_slice() {
+        let track = Track::from_deltas(vec![(0, NoteOn(Ch1, 60, 100)),
+                                            (96, NoteOff(Ch1, 60, 0)),
+                                            (0, NoteOn(Ch1, 62, 100)),
+                                            (96, NoteOff(Ch1, 62, 0))]).unwrap();
+        let slice = track.slice(96, 192);
+        assert_eq!(slice.to_deltas(), vec![(0, NoteOff(Ch1, 60, 0)),
+                                           (0, NoteOn(Ch1, 62, 100))]);
+        assert!(track.slice(200, 300).is_empty());
+    }
+
+    #[test]
+    fn test_quantize() {
+        let mut track = Track::from_deltas(vec![(5, NoteOn(Ch1, 60, 100)),
+                                                (40, NoteOn(Ch1, 62, 100)),
+                                                (3, NoteOff(Ch1, 60, 0)),
+                                                (50, NoteOff(Ch1, 62, 0))]).unwrap();
+        track.quantize(48);
+        assert_eq!(track.to_deltas(), vec![(0, NoteOn(Ch1, 60, 100)),
+                                           (48, NoteOff(Ch1, 60, 0)),
+                                           (0, NoteOn(Ch1, 62, 100)),
+                                           (48, NoteOff(Ch1, 62, 0))]);
+    }
+
+    #[test]
+    fn test_quantize_short_note() {
+        // both ends of the first note round to tick 48
+        let mut track = Track::from_deltas(vec![(40, NoteOn(Ch1, 60, 100)),
+                                                (10, NoteOff(Ch1, 60, 0)),
+                                                (0, NoteOn(Ch1, 62, 100)),
+                                                (2, NoteOn(Ch1, 62, 0))]).unwrap();
+        track.quantize(48);
+        assert_eq!(track.to_deltas(), vec![(48, NoteOn(Ch1, 60, 100)),
+                                           (0, NoteOn(Ch1, 62, 100)),
+                                           (48, NoteOff(Ch1, 60, 0)),
+                                           (0, NoteOn(Ch1, 62, 0))]);
+    }
+}
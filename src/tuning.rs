@@ -0,0 +1,486 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use num::FromPrimitive;
+
+use constants::{UNIVERSAL_NON_REAL_TIME, RPN_TUNING_PROGRAM, RPN_TUNING_BANK};
+use types::{Channel, U7, U14};
+use message::Message;
+use Message::{SysEx, RPN7};
+use note::Note;
+use universal::{UniversalSysEx, NonRealTime, RealTime};
+use utils::{mask7, msb_lsb_to_u14, u14_to_msb_lsb};
+
+/// The sub-ID#2s of the Midi Tuning Standard messages
+const BULK_DUMP_REQUEST: U7 = 0x00;
+const BULK_DUMP: U7 = 0x01;
+const SINGLE_NOTE: U7 = 0x02;
+const SINGLE_NOTE_BANK: U7 = 0x07;
+const SCALE_OCTAVE_1_BYTE: U7 = 0x08;
+const SCALE_OCTAVE_2_BYTE: U7 = 0x09;
+
+/// The number of fractions in a semitone
+const FRACTIONS: i64 = 1 << 14;
+/// The length of the name in a bulk tuning dump
+const NAME_LENGTH: usize = 16;
+
+/// A pitch or interval in cents, 1/100 of an equal tempered semitone
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Cents(pub f64);
+
+/// A frequency in Hz
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Hz(pub f64);
+
+/// A pitch in the Midi Tuning Standard, a note plus a fraction of a semitone above it
+///
+/// The fraction is in units of 100/16384 cents. The highest pitch, note 127 plus 16383, is reserved
+/// to mean "no change", so can't be used.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TuningPitch {
+    pub note: Note,
+    pub fraction: U14
+}
+
+impl TuningPitch {
+    pub fn new(note: Note, fraction: U14) -> TuningPitch {
+        TuningPitch { note, fraction }
+    }
+
+    /// The pitch `cents` above note 0, rounded to the nearest fraction, returns `None` if it is
+    /// out of range
+    pub fn from_cents(cents: Cents) -> Option<TuningPitch> {
+        let units = (cents.0 * FRACTIONS as f64 / 100.0).round();
+        if units >= 0.0 && units < (128 * FRACTIONS - 1) as f64 {
+            let units = units as i64;
+            Some(TuningPitch::new(Note((units / FRACTIONS) as U7), (units % FRACTIONS) as U14))
+        }
+        else {
+            None
+        }
+    }
+
+    /// The number of cents above note 0
+    pub fn to_cents(&self) -> Cents {
+        Cents(self.note.0 as f64 * 100.0 + self.fraction as f64 * 100.0 / FRACTIONS as f64)
+    }
+
+    /// The pitch of a frequency in Hz, with A4 (note 69) at 440Hz, returns `None` if it is out of
+    /// range
+    pub fn from_frequency(frequency: Hz) -> Option<TuningPitch> {
+        TuningPitch::from_cents(Cents(6900.0 + 1200.0 * (frequency.0 / 440.0).log2()))
+    }
+
+    /// The frequency in Hz, with A4 (note 69) at 440Hz
+    pub fn to_frequency(&self) -> Hz {
+        Hz(440.0 * ((self.to_cents().0 - 6900.0) / 1200.0).exp2())
+    }
+
+    fn to_u7s(pitch: Option<TuningPitch>) -> [U7; 3] {
+        match pitch {
+            Some(pitch) => {
+                let (msb, lsb) = u14_to_msb_lsb(pitch.fraction);
+                [mask7(pitch.note.0), msb, lsb]
+            },
+            None => [0x7F, 0x7F, 0x7F]
+        }
+    }
+
+    // `None` for no change
+    fn from_u7s(xx: U7, yy: U7, zz: U7) -> Option<TuningPitch> {
+        match (xx, yy, zz) {
+            (0x7F, 0x7F, 0x7F) => None,
+            _ => Some(TuningPitch::new(Note(xx), msb_lsb_to_u14(yy, zz)))
+        }
+    }
+}
+
+/// Single Note Tuning Change, a change to the tuning of some notes in a tuning program
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SingleNoteTuning {
+    pub device: U7,
+    /// Whether to send as a real time message, which also changes sounding notes, only the form
+    /// with a bank can be sent as a non real time message
+    pub real_time: bool,
+    /// The bank of the program, `None` for the bank-less form of the message
+    pub bank: Option<U7>,
+    pub program: U7,
+    /// Notes and their new pitches
+    pub changes: Vec<(Note, TuningPitch)>
+}
+
+impl SingleNoteTuning {
+    /// Read a Single Note Tuning Change message, returns `None` if the message is not one
+    ///
+    /// Changes to "no change" are left out.
+    pub fn from_message(message: &Message) -> Option<SingleNoteTuning> {
+        let (device, real_time, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, NonRealTime::TuningStandard, data) => {
+                (device, false, data)
+            },
+            UniversalSysEx::RealTime(device, RealTime::TuningStandard, data) => {
+                (device, true, data)
+            },
+            _ => return None
+        };
+        let (bank, program, count, rest) = match data[..] {
+            [SINGLE_NOTE, program, count, ref rest @ ..] if real_time => {
+                (None, program, count, rest)
+            },
+            [SINGLE_NOTE_BANK, bank, program, count, ref rest @ ..] => {
+                (Some(bank), program, count, rest)
+            },
+            _ => return None
+        };
+        if rest.len() != count as usize * 4 {
+            return None;
+        }
+        let changes = rest.chunks(4)
+            .filter_map(|c| TuningPitch::from_u7s(c[1], c[2], c[3]).map(|p| (Note(c[0]), p)))
+            .collect();
+        Some(SingleNoteTuning { device, real_time, bank, program, changes })
+    }
+
+    /// The message, at most 127 changes can be sent in one message, a bank-less tuning is always
+    /// sent as a real time message
+    pub fn to_message(&self) -> Message {
+        let mut data = match self.bank {
+            Some(bank) => vec![SINGLE_NOTE_BANK, mask7(bank)],
+            None => vec![SINGLE_NOTE]
+        };
+        let changes = &self.changes[..self.changes.len().min(127)];
+        data.extend_from_slice(&[mask7(self.program), changes.len() as U7]);
+        for &(note, pitch) in changes {
+            data.push(mask7(note.0));
+            data.extend_from_slice(&TuningPitch::to_u7s(Some(pitch)));
+        }
+        if self.real_time || self.bank.is_none() {
+            UniversalSysEx::RealTime(self.device, RealTime::TuningStandard, data).to_message()
+        }
+        else {
+            UniversalSysEx::NonRealTime(self.device, NonRealTime::TuningStandard, data).to_message()
+        }
+    }
+}
+
+/// Bulk Tuning Dump Request, asks `device` to send a bulk tuning dump of a tuning program
+pub fn bulk_tuning_dump_request(device: U7, program: U7) -> Message {
+    UniversalSysEx::NonRealTime(device, NonRealTime::TuningStandard,
+                                vec![BULK_DUMP_REQUEST, mask7(program)]).to_message()
+}
+
+/// Bulk Tuning Dump, the pitches of all 128 notes in a tuning program
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BulkTuningDump {
+    pub device: U7,
+    pub program: U7,
+    /// The name of the program, sent as 16 ASCII characters padded with spaces
+    pub name: String,
+    /// The pitch of each note, `None` for no change, missing notes are sent as no change
+    pub pitches: Vec<Option<TuningPitch>>
+}
+
+impl BulkTuningDump {
+    /// Read a Bulk Tuning Dump message, returns `None` if the message is not one or the checksum
+    /// is wrong
+    pub fn from_message(message: &Message) -> Option<BulkTuningDump> {
+        let (device, data) = match *message {
+            SysEx(_, ref data) => match UniversalSysEx::from_message(message)? {
+                UniversalSysEx::NonRealTime(device, NonRealTime::TuningStandard, _) => {
+                    (device, data)
+                },
+                _ => return None
+            },
+            _ => return None
+        };
+        let (checksum, body) = data.split_last()?;
+        if body.len() != 2 + 2 + NAME_LENGTH + 128 * 3 || body[2] != BULK_DUMP ||
+            bulk_dump_checksum(body) != *checksum {
+            return None;
+        }
+        let (name, pitches) = body[4..].split_at(NAME_LENGTH);
+        Some(BulkTuningDump {
+            device,
+            program: body[3],
+            name: name.iter().map(|b| *b as char).collect::<String>().trim_end().to_owned(),
+            pitches: pitches.chunks(3).map(|p| TuningPitch::from_u7s(p[0], p[1], p[2])).collect()
+        })
+    }
+
+    pub fn to_message(&self) -> Message {
+        let mut data = vec![BULK_DUMP, mask7(self.program)];
+        let name = self.name.bytes().chain(::std::iter::repeat(b' ')).take(NAME_LENGTH);
+        data.extend(name.map(mask7));
+        for i in 0..128 {
+            data.extend_from_slice(&TuningPitch::to_u7s(self.pitches.get(i).cloned().flatten()));
+        }
+        let mut checked = vec![mask7(self.device), NonRealTime::TuningStandard.to_u7()];
+        checked.extend_from_slice(&data);
+        data.push(bulk_dump_checksum(&checked));
+        UniversalSysEx::NonRealTime(self.device, NonRealTime::TuningStandard, data).to_message()
+    }
+}
+
+// the XOR of every byte from the sub-ID (0x7E) to the last data byte
+fn bulk_dump_checksum(data: &[U7]) -> U7 {
+    data.iter().fold(UNIVERSAL_NON_REAL_TIME, |checksum, b| checksum ^ b) & 0x7F
+}
+
+/// The offsets of the 12 pitch classes (starting at C) in a scale/octave tuning
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ScaleOffsets {
+    /// The 1 byte form, in whole cents from -64 to +63
+    Cents([i8; 12]),
+    /// The 2 byte form, in units of 100/8192 cents from -8192 (-100 cents) to +8191
+    Fine([i16; 12])
+}
+
+impl ScaleOffsets {
+    /// The 2 byte form of offsets in cents, rounded to the nearest unit
+    pub fn fine_from_cents(cents: [Cents; 12]) -> ScaleOffsets {
+        let mut fine = [0; 12];
+        for (f, c) in fine.iter_mut().zip(cents.iter()) {
+            *f = (c.0 * 8192.0 / 100.0).round().clamp(-8192.0, 8191.0) as i16;
+        }
+        ScaleOffsets::Fine(fine)
+    }
+
+    /// The offsets in cents
+    pub fn to_cents(&self) -> [Cents; 12] {
+        let mut cents = [Cents(0.0); 12];
+        for (i, c) in cents.iter_mut().enumerate() {
+            *c = Cents(match *self {
+                ScaleOffsets::Cents(ref offsets) => offsets[i] as f64,
+                ScaleOffsets::Fine(ref offsets) => offsets[i] as f64 * 100.0 / 8192.0
+            });
+        }
+        cents
+    }
+}
+
+/// Scale/Octave Tuning, sets the offset of each pitch class on some channels, so the same
+/// offset applies to that note in every octave
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ScaleOctaveTuning {
+    pub device: U7,
+    /// Whether to send as a real time message, which also changes sounding notes
+    pub real_time: bool,
+    pub channels: Vec<Channel>,
+    pub offsets: ScaleOffsets
+}
+
+impl ScaleOctaveTuning {
+    /// Read a Scale/Octave Tuning message, returns `None` if the message is not one
+    pub fn from_message(message: &Message) -> Option<ScaleOctaveTuning> {
+        let (device, real_time, data) = match UniversalSysEx::from_message(message)? {
+            UniversalSysEx::NonRealTime(device, NonRealTime::TuningStandard, data) => {
+                (device, false, data)
+            },
+            UniversalSysEx::RealTime(device, RealTime::TuningStandard, data) => {
+                (device, true, data)
+            },
+            _ => return None
+        };
+        let (id, ff, gg, hh, rest) = match data[..] {
+            [id, ff, gg, hh, ref rest @ ..] => (id, ff, gg, hh, rest),
+            _ => return None
+        };
+        let mut offsets = match (id, rest.len()) {
+            (SCALE_OCTAVE_1_BYTE, 12) => ScaleOffsets::Cents([0; 12]),
+            (SCALE_OCTAVE_2_BYTE, 24) => ScaleOffsets::Fine([0; 12]),
+            _ => return None
+        };
+        match offsets {
+            ScaleOffsets::Cents(ref mut offsets) => {
+                for (o, ss) in offsets.iter_mut().zip(rest) {
+                    *o = *ss as i8 - 64;
+                }
+            },
+            ScaleOffsets::Fine(ref mut offsets) => {
+                for (o, st) in offsets.iter_mut().zip(rest.chunks(2)) {
+                    *o = msb_lsb_to_u14(st[0], st[1]) as i16 - 8192;
+                }
+            }
+        }
+        let mask = (ff as u16 & 0b11) << 14 | (gg as u16 & 0x7F) << 7 | hh as u16 & 0x7F;
+        let channels = (0..16).filter(|ch| mask & (1 << ch) != 0)
+            .filter_map(Channel::from_u8)
+            .collect();
+        Some(ScaleOctaveTuning { device, real_time, channels, offsets })
+    }
+
+    pub fn to_message(&self) -> Message {
+        let mask = self.channels.iter().fold(0u16, |mask, ch| mask | 1 << *ch as u16);
+        let channels = [(mask >> 14) as U7, (mask >> 7) as U7 & 0x7F, mask as U7 & 0x7F];
+        let mut data = match self.offsets {
+            ScaleOffsets::Cents(_) => vec![SCALE_OCTAVE_1_BYTE],
+            ScaleOffsets::Fine(_) => vec![SCALE_OCTAVE_2_BYTE]
+        };
+        data.extend_from_slice(&channels);
+        match self.offsets {
+            ScaleOffsets::Cents(ref offsets) => {
+                data.extend(offsets.iter().map(|o| (o.clamp(&-64, &63) + 64) as U7));
+            },
+            ScaleOffsets::Fine(ref offsets) => {
+                for o in offsets {
+                    let (msb, lsb) = u14_to_msb_lsb((o.clamp(&-8192, &8191) + 8192) as U14);
+                    data.extend_from_slice(&[msb, lsb]);
+                }
+            }
+        }
+        if self.real_time {
+            UniversalSysEx::RealTime(self.device, RealTime::TuningStandard, data).to_message()
+        }
+        else {
+            UniversalSysEx::NonRealTime(self.device, NonRealTime::TuningStandard, data).to_message()
+        }
+    }
+}
+
+/// Select a tuning program on a channel, via its RPN
+pub fn tuning_program_select(channel: Channel, program: U7) -> Message {
+    RPN7(channel, RPN_TUNING_PROGRAM, mask7(program))
+}
+
+/// Select a tuning bank on a channel, via its RPN, takes effect at the next tuning program select
+pub fn tuning_bank_select(channel: Channel, bank: U7) -> Message {
+    RPN7(channel, RPN_TUNING_BANK, mask7(bank))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::ALL_CALL;
+    use manufacturer::Manufacturer::*;
+    use types::Channel::*;
+
+    #[test]
+    fn test_pitch() {
+        let a4 = TuningPitch::new(Note(69), 0);
+        assert_eq!(a4.to_frequency(), Hz(440.0));
+        assert_eq!(TuningPitch::from_frequency(Hz(440.0)), Some(a4));
+        assert_eq!(TuningPitch::from_frequency(Hz(880.0)), Some(TuningPitch::new(Note(81), 0)));
+        // a quarter tone above middle C
+        assert_eq!(TuningPitch::from_cents(Cents(6050.0)), Some(TuningPitch::new(Note(60), 8192)));
+        assert_eq!(TuningPitch::new(Note(60), 8192).to_cents(), Cents(6050.0));
+        // 8.1758Hz is note 0
+        assert_eq!(TuningPitch::from_frequency(Hz(8.1758)), Some(TuningPitch::new(Note(0), 0)));
+        assert_eq!(TuningPitch::from_cents(Cents(-1.0)), None);
+        assert_eq!(TuningPitch::from_cents(Cents(12800.0)), None);
+        // the highest pitch is reserved
+        assert_eq!(TuningPitch::from_cents(Cents(12799.997)), None);
+        assert_eq!(TuningPitch::from_cents(Cents(12799.99)),
+                   Some(TuningPitch::new(Note(127), 16382)));
+    }
+
+    #[test]
+    fn test_single_note() {
+        let tuning = SingleNoteTuning {
+            device: ALL_CALL,
+            real_time: true,
+            bank: None,
+            program: 0,
+            changes: vec![(Note(69), TuningPitch::new(Note(69), 0x2000)),
+                          (Note(70), TuningPitch::new(Note(69), 0x3FFF))]
+        };
+        let message = SysEx(OneByte(0x7F), vec![0x7F, 0x08, 0x02, 0x00, 0x02,
+                                                0x45, 0x45, 0x40, 0x00,
+                                                0x46, 0x45, 0x7F, 0x7F]);
+        assert_eq!(tuning.to_message(), message);
+        assert_eq!(SingleNoteTuning::from_message(&message), Some(tuning.clone()));
+
+        let bank = SingleNoteTuning { bank: Some(3), ..tuning.clone() };
+        assert_eq!(SingleNoteTuning::from_message(&bank.to_message()), Some(bank.clone()));
+
+        // the non real time form with a bank
+        let non_real_time = SingleNoteTuning { real_time: false, ..bank };
+        let message = non_real_time.to_message();
+        assert_eq!(&message, &SysEx(OneByte(0x7E), vec![0x7F, 0x08, 0x07, 0x03, 0x00, 0x02,
+                                                         0x45, 0x45, 0x40, 0x00,
+                                                         0x46, 0x45, 0x7F, 0x7F]));
+        assert_eq!(SingleNoteTuning::from_message(&message), Some(non_real_time));
+        // the bank-less form is only real time
+        let bank_less = SysEx(OneByte(0x7E), vec![0x7F, 0x08, 0x02, 0x00, 0x01,
+                                                  0x45, 0x45, 0x40, 0x00]);
+        assert_eq!(SingleNoteTuning::from_message(&bank_less), None);
+        let bank_less = SingleNoteTuning { real_time: false, ..tuning };
+        assert!(SingleNoteTuning::from_message(&bank_less.to_message()).unwrap().real_time);
+
+        let no_change = SysEx(OneByte(0x7F), vec![0x7F, 0x08, 0x02, 0x00, 0x01,
+                                                  0x45, 0x7F, 0x7F, 0x7F]);
+        assert_eq!(SingleNoteTuning::from_message(&no_change).unwrap().changes, vec![]);
+        let short = SysEx(OneByte(0x7F), vec![0x7F, 0x08, 0x02, 0x00, 0x02,
+                                              0x45, 0x45, 0x40, 0x00]);
+        assert_eq!(SingleNoteTuning::from_message(&short), None);
+    }
+
+    #[test]
+    fn test_bulk_dump() {
+        assert_eq!(bulk_tuning_dump_request(0x10, 5),
+                   SysEx(OneByte(0x7E), vec![0x10, 0x08, 0x00, 0x05]));
+
+        let pitches = (0..128)
+            .map(|n| TuningPitch::from_cents(Cents(n as f64 * 100.0 + 14.0)))
+            .collect();
+        let dump = BulkTuningDump { device: 0, program: 1, name: "Sharp".to_owned(), pitches };
+        let message = dump.to_message();
+        let data = match message {
+            SysEx(OneByte(0x7E), ref data) => data.clone(),
+            _ => panic!()
+        };
+        assert_eq!(data.len(), 2 + 2 + 16 + 384 + 1);
+        assert_eq!(&data[..9], &[0x00, 0x08, 0x01, 0x01, b'S', b'h', b'a', b'r', b'p']);
+        assert_eq!(&data[9..20], &[b' '; 11]);
+        assert_eq!(data.iter().fold(0x7E, |c, b| c ^ b) & 0x7F, 0);
+        assert_eq!(BulkTuningDump::from_message(&message), Some(dump.clone()));
+
+        // missing notes are sent as no change
+        let short = BulkTuningDump { pitches: vec![], ..dump };
+        let read = BulkTuningDump::from_message(&short.to_message()).unwrap();
+        assert_eq!(read.pitches, vec![None; 128]);
+
+        let mut bad = data.clone();
+        bad[30] ^= 1;
+        assert_eq!(BulkTuningDump::from_message(&SysEx(OneByte(0x7E), bad)), None);
+    }
+
+    #[test]
+    fn test_scale_octave() {
+        let tuning = ScaleOctaveTuning {
+            device: ALL_CALL,
+            real_time: false,
+            channels: vec![Ch1, Ch8, Ch16],
+            offsets: ScaleOffsets::Cents([0, -64, 63, 0, 0, 0, 0, 0, 0, 0, 0, -14])
+        };
+        let message = SysEx(OneByte(0x7E), vec![0x7F, 0x08, 0x08, 0x02, 0x01, 0x01,
+                                                0x40, 0x00, 0x7F, 0x40, 0x40, 0x40,
+                                                0x40, 0x40, 0x40, 0x40, 0x40, 0x32]);
+        assert_eq!(tuning.to_message(), message);
+        assert_eq!(ScaleOctaveTuning::from_message(&message), Some(tuning.clone()));
+
+        let mut cents = [Cents(0.0); 12];
+        cents[1] = Cents(-100.0);
+        cents[2] = Cents(50.0);
+        let offsets = ScaleOffsets::fine_from_cents(cents);
+        assert_eq!(offsets.to_cents(), cents);
+        let fine = ScaleOctaveTuning { real_time: true, offsets, ..tuning };
+        let message = fine.to_message();
+        match message {
+            SysEx(OneByte(0x7F), ref data) => {
+                assert_eq!(&data[2..6], &[0x09, 0x02, 0x01, 0x01]);
+                assert_eq!(&data[6..12], &[0x40, 0x00, 0x00, 0x00, 0x60, 0x00]);
+            },
+            _ => panic!()
+        }
+        assert_eq!(ScaleOctaveTuning::from_message(&message), Some(fine));
+    }
+
+    #[test]
+    fn test_program_select() {
+        assert_eq!(tuning_program_select(Ch1, 5), RPN7(Ch1, 3, 5));
+        assert_eq!(tuning_bank_select(Ch2, 1), RPN7(Ch2, 4, 1));
+    }
+}
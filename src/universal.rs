@@ -0,0 +1,235 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use constants::{UNIVERSAL_NON_REAL_TIME, UNIVERSAL_REAL_TIME, ALL_CALL};
+use types::U7;
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::SysEx;
+use utils::mask7;
+
+/// The sub-ID#1 of a Universal Non Real Time SysEx
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum NonRealTime {
+    SampleDumpHeader,
+    SampleDataPacket,
+    SampleDumpRequest,
+    MtcCueing,
+    SampleDumpExtensions,
+    /// Includes the Identity Request and Reply
+    GeneralInformation,
+    FileDump,
+    TuningStandard,
+    GeneralMidi,
+    DownloadableSounds,
+    FileReference,
+    VisualControl,
+    CapabilityInquiry,
+    EndOfFile,
+    Wait,
+    Cancel,
+    Nak,
+    Ack,
+    Other(U7)
+}
+
+const NON_REAL_TIME_IDS: [(NonRealTime, U7); 18] = [
+    (NonRealTime::SampleDumpHeader, 0x01),
+    (NonRealTime::SampleDataPacket, 0x02),
+    (NonRealTime::SampleDumpRequest, 0x03),
+    (NonRealTime::MtcCueing, 0x04),
+    (NonRealTime::SampleDumpExtensions, 0x05),
+    (NonRealTime::GeneralInformation, 0x06),
+    (NonRealTime::FileDump, 0x07),
+    (NonRealTime::TuningStandard, 0x08),
+    (NonRealTime::GeneralMidi, 0x09),
+    (NonRealTime::DownloadableSounds, 0x0A),
+    (NonRealTime::FileReference, 0x0B),
+    (NonRealTime::VisualControl, 0x0C),
+    (NonRealTime::CapabilityInquiry, 0x0D),
+    (NonRealTime::EndOfFile, 0x7B),
+    (NonRealTime::Wait, 0x7C),
+    (NonRealTime::Cancel, 0x7D),
+    (NonRealTime::Nak, 0x7E),
+    (NonRealTime::Ack, 0x7F)
+];
+
+impl NonRealTime {
+    pub fn from_u7(id: U7) -> NonRealTime {
+        NON_REAL_TIME_IDS.iter().find(|p| p.1 == id).map_or(NonRealTime::Other(id), |p| p.0)
+    }
+
+    pub fn to_u7(self) -> U7 {
+        match self {
+            NonRealTime::Other(id) => mask7(id),
+            _ => NON_REAL_TIME_IDS.iter().find(|p| p.0 == self).map_or(0, |p| p.1)
+        }
+    }
+}
+
+/// The sub-ID#1 of a Universal Real Time SysEx
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum RealTime {
+    /// Midi Time Code, includes the Full Frame message
+    Mtc,
+    ShowControl,
+    NotationInformation,
+    DeviceControl,
+    MtcCueing,
+    MachineControlCommand,
+    MachineControlResponse,
+    TuningStandard,
+    ControllerDestination,
+    KeyBasedInstrumentControl,
+    ScalablePolyphony,
+    MobilePhoneControl,
+    Other(U7)
+}
+
+const REAL_TIME_IDS: [(RealTime, U7); 12] = [
+    (RealTime::Mtc, 0x01),
+    (RealTime::ShowControl, 0x02),
+    (RealTime::NotationInformation, 0x03),
+    (RealTime::DeviceControl, 0x04),
+    (RealTime::MtcCueing, 0x05),
+    (RealTime::MachineControlCommand, 0x06),
+    (RealTime::MachineControlResponse, 0x07),
+    (RealTime::TuningStandard, 0x08),
+    (RealTime::ControllerDestination, 0x09),
+    (RealTime::KeyBasedInstrumentControl, 0x0A),
+    (RealTime::ScalablePolyphony, 0x0B),
+    (RealTime::MobilePhoneControl, 0x0C)
+];
+
+impl RealTime {
+    pub fn from_u7(id: U7) -> RealTime {
+        REAL_TIME_IDS.iter().find(|p| p.1 == id).map_or(RealTime::Other(id), |p| p.0)
+    }
+
+    pub fn to_u7(self) -> U7 {
+        match self {
+            RealTime::Other(id) => mask7(id),
+            _ => REAL_TIME_IDS.iter().find(|p| p.0 == self).map_or(0, |p| p.1)
+        }
+    }
+}
+
+/// A Universal SysEx message, as defined by the Midi spec rather than a manufacturer
+///
+/// The first argument is the device ID (`ALL_CALL` for all devices).
+/// The second argument is the sub-ID#1, which gives the family of the message.
+/// The third argument is the rest of the data, starting with the sub-ID#2 if the message has one.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum UniversalSysEx {
+    NonRealTime(U7, NonRealTime, Vec<U7>),
+    RealTime(U7, RealTime, Vec<U7>)
+}
+
+impl UniversalSysEx {
+    /// Read a `Message::SysEx`, returns `None` if it isn't a Universal SysEx
+    pub fn from_message(message: &Message) -> Option<UniversalSysEx> {
+        let (manufacturer, data) = match *message {
+            SysEx(Manufacturer::OneByte(m), ref data) => (m, data),
+            _ => return None
+        };
+        match (manufacturer, &data[..]) {
+            (UNIVERSAL_NON_REAL_TIME, &[device, id, ref rest @ ..]) => {
+                Some(UniversalSysEx::NonRealTime(device, NonRealTime::from_u7(id), rest.to_vec()))
+            },
+            (UNIVERSAL_REAL_TIME, &[device, id, ref rest @ ..]) => {
+                Some(UniversalSysEx::RealTime(device, RealTime::from_u7(id), rest.to_vec()))
+            },
+            _ => None
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        let (manufacturer, device, id, data) = match *self {
+            UniversalSysEx::NonRealTime(device, id, ref data) => {
+                (UNIVERSAL_NON_REAL_TIME, device, id.to_u7(), data)
+            },
+            UniversalSysEx::RealTime(device, id, ref data) => {
+                (UNIVERSAL_REAL_TIME, device, id.to_u7(), data)
+            }
+        };
+        let mut bytes = vec![mask7(device), id];
+        bytes.extend_from_slice(data);
+        SysEx(Manufacturer::OneByte(manufacturer), bytes)
+    }
+
+    pub fn device(&self) -> U7 {
+        match *self {
+            UniversalSysEx::NonRealTime(device, _, _) | UniversalSysEx::RealTime(device, _, _) => {
+                device
+            }
+        }
+    }
+
+    /// Returns `true` if a device with ID `device` should respond to this message, either it is
+    /// addressed to `device` or to all devices
+    pub fn is_for(&self, device: U7) -> bool {
+        self.device() == ALL_CALL || self.device() == device
+    }
+
+    /// The data after the sub-ID#1
+    pub fn data(&self) -> &[U7] {
+        match *self {
+            UniversalSysEx::NonRealTime(_, _, ref data) |
+            UniversalSysEx::RealTime(_, _, ref data) => data
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manufacturer::Manufacturer::*;
+
+    #[test]
+    fn test_round_trip() {
+        let identity = SysEx(OneByte(0x7E), vec![0x7F, 0x06, 0x01]);
+        let universal = UniversalSysEx::from_message(&identity).unwrap();
+        assert_eq!(universal, UniversalSysEx::NonRealTime(ALL_CALL,
+                                                          NonRealTime::GeneralInformation,
+                                                          vec![0x01]));
+        assert_eq!(universal.to_message(), identity);
+
+        let mmc = SysEx(OneByte(0x7F), vec![0x10, 0x06, 0x02]);
+        let universal = UniversalSysEx::from_message(&mmc).unwrap();
+        assert_eq!(universal, UniversalSysEx::RealTime(0x10,
+                                                       RealTime::MachineControlCommand,
+                                                       vec![0x02]));
+        assert_eq!(universal.to_message(), mmc);
+
+        let other = SysEx(OneByte(0x7E), vec![0x00, 0x20]);
+        let universal = UniversalSysEx::from_message(&other).unwrap();
+        assert_eq!(universal, UniversalSysEx::NonRealTime(0, NonRealTime::Other(0x20), vec![]));
+        assert_eq!(universal.to_message(), other);
+    }
+
+    #[test]
+    fn test_not_universal() {
+        assert_eq!(UniversalSysEx::from_message(&SysEx(OneByte(0x41), vec![0, 1, 2])), None);
+        assert_eq!(UniversalSysEx::from_message(&SysEx(OneByte(0x7E), vec![0])), None);
+        assert_eq!(UniversalSysEx::from_message(&Message::Start), None);
+    }
+
+    #[test]
+    fn test_ids() {
+        for id in 0..128 {
+            assert_eq!(NonRealTime::from_u7(id).to_u7(), id);
+            assert_eq!(RealTime::from_u7(id).to_u7(), id);
+        }
+    }
+
+    #[test]
+    fn test_device() {
+        let message = UniversalSysEx::RealTime(ALL_CALL, RealTime::Mtc, vec![]);
+        assert!(message.is_for(0x10));
+        let message = UniversalSysEx::RealTime(0x10, RealTime::Mtc, vec![1, 2]);
+        assert!(message.is_for(0x10) && !message.is_for(0x11));
+        assert_eq!((message.device(), message.data()), (0x10, &[1, 2][..]));
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2015 Sam Doshi (sam@metal-fish.co.uk)
+//
+// Licensed under the MIT License <LICENSE or http://opensource.org/licenses/MIT>.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use types::U7;
+use manufacturer::Manufacturer;
+use message::Message;
+use Message::SysEx;
+use utils::mask7;
+
+/// Yamaha's manufacturer ID
+pub const YAMAHA: Manufacturer = Manufacturer::OneByte(0x43);
+
+/// The model ID used by all XG devices
+pub const XG_MODEL: U7 = 0x4C;
+
+const PARAMETER_CHANGE: U7 = 0x10;
+
+/// XG Parameter Change, sets the parameters starting at `address` to `data`
+///
+/// `device` is the device number, 0-15.
+pub fn xg_parameter_change(device: U7, address: [U7; 3], data: &[U7]) -> Message {
+    let mut bytes = vec![PARAMETER_CHANGE | (device & 0x0F), XG_MODEL];
+    bytes.extend(address.iter().chain(data).map(|b| mask7(*b)));
+    SysEx(YAMAHA, bytes)
+}
+
+/// XG System On, resets an XG device to its XG defaults
+pub fn xg_system_on(device: U7) -> Message {
+    xg_parameter_change(device, [0x00, 0x00, 0x7E], &[0x00])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xg() {
+        // F0 43 10 4C 00 00 7E 00 F7
+        assert_eq!(xg_system_on(0), SysEx(YAMAHA, vec![0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00]));
+        // reverb type on device 2
+        assert_eq!(xg_parameter_change(2, [0x02, 0x01, 0x00], &[0x01, 0x00]),
+                   SysEx(YAMAHA, vec![0x12, 0x4C, 0x02, 0x01, 0x00, 0x01, 0x00]));
+    }
+}